@@ -0,0 +1,109 @@
+//! Concurrent logins on a runtime with few worker threads, comparing bcrypt
+//! called directly on the executor with `BlockingHasher` for both schemes.
+//! A probe task sleeps in a loop next to the logins; how late it wakes up
+//! shows how long the workers were unable to serve any other request.
+//!
+//! Run with `cargo bench --bench password_hashing`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::runtime::Builder;
+
+use abcd_layered_architecture::core::password::PasswordHasher;
+use abcd_layered_architecture::driver::password::blocking_hasher::BlockingHasher;
+use abcd_layered_architecture::driver::password::scheme::PasswordScheme;
+
+const PASSWORD: &str = "correct horse battery staple";
+const COST: u32 = 12;
+const WORKER_THREADS: usize = 2;
+const CONCURRENT_LOGINS: usize = 16;
+const PROBE_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Clone)]
+enum Mode {
+    OnExecutor,
+    Hasher(Arc<BlockingHasher>),
+}
+
+struct Measurement {
+    elapsed: Duration,
+    worst_probe_delay: Duration,
+}
+
+async fn login(mode: Mode, hash: String) -> bool {
+    match mode {
+        Mode::OnExecutor => bcrypt::verify(PASSWORD, &hash).unwrap(),
+        Mode::Hasher(hasher) => hasher.verify(PASSWORD, &hash).await,
+    }
+}
+
+fn run(mode: Mode, hash: &str) -> Measurement {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(WORKER_THREADS)
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let done = Arc::new(AtomicBool::new(false));
+        let probe = tokio::spawn({
+            let done = done.clone();
+            async move {
+                let mut worst = Duration::ZERO;
+                while !done.load(Ordering::Relaxed) {
+                    let start = Instant::now();
+                    tokio::time::sleep(PROBE_INTERVAL).await;
+                    worst = worst.max(start.elapsed().saturating_sub(PROBE_INTERVAL));
+                }
+                worst
+            }
+        });
+        let start = Instant::now();
+        let logins = (0..CONCURRENT_LOGINS)
+            .map(|_| tokio::spawn(login(mode.clone(), hash.to_owned())))
+            .collect::<Vec<_>>();
+        for login in logins {
+            assert!(login.await.unwrap());
+        }
+        let elapsed = start.elapsed();
+        done.store(true, Ordering::Relaxed);
+        Measurement {
+            elapsed,
+            worst_probe_delay: probe.await.unwrap(),
+        }
+    })
+}
+
+fn report(name: &str, measurement: Measurement) {
+    println!(
+        "{name:<26} {CONCURRENT_LOGINS} logins in {:>8.1?} ({:>5.1} logins/s), worst probe delay {:>8.1?}",
+        measurement.elapsed,
+        CONCURRENT_LOGINS as f64 / measurement.elapsed.as_secs_f64(),
+        measurement.worst_probe_delay,
+    );
+}
+
+fn main() {
+    let max_concurrency = std::thread::available_parallelism().map_or(1, |count| count.get());
+    let bcrypt_hasher = Arc::new(BlockingHasher::new(PasswordScheme::Bcrypt { cost: COST }, max_concurrency));
+    let argon2_hasher = Arc::new(BlockingHasher::new(
+        PasswordScheme::argon2id(19 * 1024, 2, 1).unwrap(),
+        max_concurrency,
+    ));
+    let hash = |hasher: &BlockingHasher| {
+        let hash = Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(hasher.hash(PASSWORD));
+        assert!(!hasher.needs_rehash(&hash));
+        hash
+    };
+    let bcrypt_hash = hash(&bcrypt_hasher);
+    let argon2_hash = hash(&argon2_hasher);
+
+    println!("bcrypt cost {COST}, {WORKER_THREADS} worker threads, {max_concurrency} hashing permits");
+    report("bcrypt on the executor", run(Mode::OnExecutor, &bcrypt_hash));
+    report("BlockingHasher (bcrypt)", run(Mode::Hasher(bcrypt_hasher), &bcrypt_hash));
+    report("BlockingHasher (argon2id)", run(Mode::Hasher(argon2_hasher), &argon2_hash));
+}
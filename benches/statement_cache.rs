@@ -0,0 +1,74 @@
+//! Looks up a user by name the way `UserDao::find_by_username` does, once
+//! preparing the statement on every call as before and once through the
+//! statement cache of `ClientAdapter`. Each variant runs on a pool of its
+//! own so the cache starts empty.
+//!
+//! Needs the database of the server, configured through the same settings
+//! file or `PG_*` variables. Run with `cargo bench --bench statement_cache`.
+
+use std::time::{Duration, Instant};
+
+use deadpool_postgres::{Pool, Runtime};
+use tokio::runtime::Builder;
+
+use abcd_layered_architecture::driver::database::client_adapter::{ClientAdapter, StatementCacheMetrics};
+use abcd_layered_architecture::driver::database::config_factory::ConfigFactory;
+use abcd_layered_architecture::settings::{Cli, Settings};
+
+const STATEMENT: &str = "SELECT * FROM Users WHERE username=$1";
+const USERNAME: &str = "statement_cache_bench";
+const LOOKUPS: u32 = 2_000;
+
+/// Only the database part is used, so the rest is not validated.
+fn pool() -> Pool {
+    let settings = Settings::read(&Cli::default(), |name| std::env::var(name).ok()).expect("invalid settings");
+    let config_factory = ConfigFactory::new(settings.database);
+    let tls = config_factory.create_tls().expect("could not set up postgres tls");
+    config_factory
+        .create()
+        .expect("invalid postgres settings")
+        .create_pool(Some(Runtime::Tokio1), tls)
+        .expect("could not create postgres connection pool")
+}
+
+async fn uncached(pool: &Pool) -> Duration {
+    let client = pool.get().await.unwrap();
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        let stmt = client.prepare(STATEMENT).await.unwrap();
+        client.query(&stmt, &[&USERNAME]).await.unwrap();
+    }
+    start.elapsed()
+}
+
+async fn cached(pool: &Pool) -> Duration {
+    let client = pool.get().await.unwrap();
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        let stmt = ClientAdapter::prepare(&client, STATEMENT).await.unwrap();
+        ClientAdapter::query(&client, stmt, &[&USERNAME]).await.unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{name:<22} {LOOKUPS} lookups in {:>8.1?} ({:>6.1?} per lookup)",
+        elapsed,
+        elapsed / LOOKUPS,
+    );
+}
+
+fn main() {
+    let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        if let Err(err) = pool().get().await {
+            println!("skipped, no database reachable with the configured settings: {err}");
+            return;
+        }
+        report("prepare on every call", uncached(&pool()).await);
+        report("statement cache", cached(&pool()).await);
+        let metrics = StatementCacheMetrics::global();
+        println!("statement cache hits {}, misses {}", metrics.hits(), metrics.misses());
+    });
+}
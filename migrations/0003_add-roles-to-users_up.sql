@@ -0,0 +1 @@
+ALTER TABLE Users ADD COLUMN roles VARCHAR[] NOT NULL DEFAULT '{user}';
@@ -0,0 +1 @@
+ALTER TABLE Tokens DROP COLUMN family_id;
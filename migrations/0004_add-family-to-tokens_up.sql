@@ -0,0 +1 @@
+ALTER TABLE Tokens ADD COLUMN family_id uuid NOT NULL DEFAULT gen_random_uuid();
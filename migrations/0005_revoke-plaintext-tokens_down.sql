@@ -0,0 +1,2 @@
+-- plaintext keys cannot be restored from their revoked placeholders
+SELECT 1;
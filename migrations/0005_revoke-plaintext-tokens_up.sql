@@ -0,0 +1 @@
+UPDATE Tokens SET key = 'legacy:' || id, is_revoked = true;
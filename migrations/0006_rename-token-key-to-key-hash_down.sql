@@ -0,0 +1 @@
+ALTER TABLE Tokens RENAME COLUMN key_hash TO key;
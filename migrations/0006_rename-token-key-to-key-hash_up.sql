@@ -0,0 +1 @@
+ALTER TABLE Tokens RENAME COLUMN key TO key_hash;
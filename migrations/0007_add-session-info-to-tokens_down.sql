@@ -0,0 +1,5 @@
+ALTER TABLE Tokens
+    DROP COLUMN created_at,
+    DROP COLUMN last_used_at,
+    DROP COLUMN user_agent,
+    DROP COLUMN ip_address;
@@ -0,0 +1,5 @@
+ALTER TABLE Tokens
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT now(),
+    ADD COLUMN last_used_at TIMESTAMP NOT NULL DEFAULT now(),
+    ADD COLUMN user_agent VARCHAR,
+    ADD COLUMN ip_address VARCHAR;
@@ -0,0 +1 @@
+ALTER TABLE Tokens ADD COLUMN purpose VARCHAR NOT NULL DEFAULT 'refresh';
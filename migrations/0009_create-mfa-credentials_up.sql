@@ -0,0 +1,7 @@
+CREATE TABLE MfaCredentials (
+    user_id uuid PRIMARY KEY REFERENCES Users (id) ON DELETE CASCADE,
+    secret BYTEA NOT NULL,
+    is_confirmed BOOLEAN NOT NULL DEFAULT false,
+    recovery_codes VARCHAR[] NOT NULL DEFAULT '{}',
+    last_used_step BIGINT NOT NULL DEFAULT 0
+)
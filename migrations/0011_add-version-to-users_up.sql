@@ -0,0 +1,2 @@
+ALTER TABLE Users
+    ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use actix_web::web::{scope, Data, ServiceConfig};
+
+use crate::api::auth::signing_key::KeyStore;
+use crate::api::middleware::rate_limit_store::{RateLimitStore, ShardedMemoryStore};
+use crate::api::routes;
+use crate::business::auth::service::AuthService;
+use crate::business::user::service::UserService;
+use crate::settings::Settings;
+
+/// Mounts the auth and user routes, together with the services they need,
+/// into any actix application via `App::configure`. Everything is registered
+/// on its own scope, so the host application keeps its app data, extractor
+/// configs and routes outside of the prefix.
+#[derive(Clone)]
+pub struct AppBuilder {
+    prefix: String,
+    settings: Arc<Settings>,
+    user_service: Arc<UserService>,
+    auth_service: Arc<AuthService>,
+    key_store: Arc<KeyStore>,
+    rate_limit_store: Arc<dyn RateLimitStore>,
+}
+
+impl AppBuilder {
+    pub fn new(
+        settings: Arc<Settings>,
+        user_service: Arc<UserService>,
+        auth_service: Arc<AuthService>,
+        key_store: Arc<KeyStore>,
+    ) -> Self {
+        Self {
+            prefix: String::new(),
+            settings,
+            user_service,
+            auth_service,
+            key_store,
+            rate_limit_store: Arc::new(ShardedMemoryStore::new()),
+        }
+    }
+    /// Path every route is mounted under, e.g. `/auth`. Empty by default.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_owned();
+        self
+    }
+    /// Rate limit buckets are kept in process memory unless replaced here,
+    /// e.g. by a store shared between instances.
+    pub fn with_rate_limit_store(mut self, rate_limit_store: Arc<dyn RateLimitStore>) -> Self {
+        self.rate_limit_store = rate_limit_store;
+        self
+    }
+    pub fn settings(&self) -> &Arc<Settings> {
+        &self.settings
+    }
+    pub fn user_service(&self) -> &Arc<UserService> {
+        &self.user_service
+    }
+    pub fn auth_service(&self) -> &Arc<AuthService> {
+        &self.auth_service
+    }
+    pub fn key_store(&self) -> &Arc<KeyStore> {
+        &self.key_store
+    }
+    pub fn configure(&self, cfg: &mut ServiceConfig) {
+        cfg.service(
+            scope(&self.prefix)
+                .app_data(Data::from(self.settings.clone()))
+                .app_data(Data::from(self.user_service.clone()))
+                .app_data(Data::from(self.auth_service.clone()))
+                .app_data(Data::from(self.key_store.clone()))
+                .configure(|cfg| routes::configure(cfg, self.rate_limit_store.clone())),
+        );
+    }
+}
@@ -3,16 +3,16 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use actix_web::dev::Payload;
 use actix_web::http::header;
+use actix_web::web::Data;
 use actix_web::{FromRequest, HttpRequest};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use log::debug;
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use serde::{Deserialize, Serialize};
 
+use crate::api::auth::signing_key::KeyStore;
+use crate::api::error::ApiError;
 use crate::core::error::AuthenticationError;
-
-const JWT_ISSUER: &str = "asdf";
-const JWT_TTL_IN_MILLIS: u128 = 1000 * 60 * 15;
-const JWT_SECRET: &str = "secret";
+use crate::core::role::Role;
+use crate::settings::JwtSettings;
 
 pub struct JsonWebToken {
     key: String,
@@ -20,9 +20,9 @@ pub struct JsonWebToken {
 }
 
 impl JsonWebToken {
-    pub fn new(username: &str) -> Self {
-        let claims = Claims::new(username);
-        JsonWebToken::encode(claims)
+    pub fn new(key_store: &KeyStore, settings: &JwtSettings, username: &str, roles: &[Role]) -> Self {
+        let claims = Claims::new(settings, username, roles);
+        JsonWebToken::encode(key_store, claims)
     }
     pub fn key(&self) -> &str {
         &self.key
@@ -30,20 +30,28 @@ impl JsonWebToken {
     pub fn username(&self) -> &str {
         self.claims.sub.as_str()
     }
-    fn encode(claims: Claims) -> Self {
-        let key = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-        )
-        .unwrap();
+    pub fn roles(&self) -> &[Role] {
+        &self.claims.roles
+    }
+    fn encode(key_store: &KeyStore, claims: Claims) -> Self {
+        let signing_key = key_store.signing_key();
+        let mut header = Header::new(signing_key.algorithm());
+        header.kid = Some(signing_key.kid().to_owned());
+        let key = encode(&header, &claims, signing_key.encoding_key()).unwrap();
         Self { key, claims }
     }
-    fn decode(key: &str) -> Result<Self, AuthenticationError> {
+    fn decode(key_store: &KeyStore, key: &str) -> Result<Self, AuthenticationError> {
+        let kid = decode_header(key)
+            .map_err(|err| AuthenticationError::new(err.to_string().as_str()))?
+            .kid
+            .ok_or(AuthenticationError::new("missing key id"))?;
+        let verification_key = key_store
+            .verification_key(&kid)
+            .ok_or(AuthenticationError::new("unknown key id"))?;
         let claims = decode::<Claims>(
             key,
-            &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-            &Validation::new(Algorithm::HS256),
+            verification_key.decoding_key(),
+            &Validation::new(verification_key.algorithm()),
         )
         .map_err(|err| AuthenticationError::new(err.to_string().as_str()))?
         .claims;
@@ -56,41 +64,48 @@ impl JsonWebToken {
 }
 
 impl FromRequest for JsonWebToken {
-    type Error = AuthenticationError;
+    type Error = ApiError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        if let Some(header) = req.headers().get(header::AUTHORIZATION) {
-            if let Ok(header_value) = header.to_str() {
-                debug!("{:?}", &header_value[7..]);
-                return ready(JsonWebToken::decode(&header_value[7..]));
-            }
+        let key_store = req
+            .app_data::<Data<KeyStore>>()
+            .expect("KeyStore is registered as app data");
+        let bearer = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header_value| header_value.strip_prefix("Bearer "));
+        if let Some(key) = bearer {
+            return ready(JsonWebToken::decode(key_store, key).map_err(ApiError::from));
         }
-        ready(Err(AuthenticationError::new(
-            "could not read json web token",
-        )))
+        ready(Err(AuthenticationError::new("could not read json web token").into()))
     }
 }
 
 #[derive(Serialize, Deserialize)]
 struct Claims {
     iss: String,
-    iat: u128,
-    exp: u128,
+    /// Seconds since the epoch, as RFC 7519 requires.
+    iat: u64,
+    exp: u64,
     sub: String,
+    #[serde(default)]
+    roles: Vec<Role>,
 }
 
 impl Claims {
-    fn new(sub: &str) -> Self {
+    fn new(settings: &JwtSettings, sub: &str, roles: &[Role]) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_millis();
+            .as_secs();
         Self {
-            iss: JWT_ISSUER.to_owned(),
+            iss: settings.issuer.to_owned(),
             iat: now,
-            exp: now + JWT_TTL_IN_MILLIS,
+            exp: now + settings.ttl().as_secs(),
             sub: sub.to_owned(),
+            roles: roles.to_vec(),
         }
     }
 }
@@ -0,0 +1,24 @@
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{FromRequest, HttpRequest};
+
+use crate::core::token::ClientInfo;
+
+/// Uses the peer address rather than `X-Forwarded-For`, which any client can set.
+impl FromRequest for ClientInfo {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let ip_address = req.peer_addr().map(|address| address.ip().to_string());
+        ready(Ok(ClientInfo::new(user_agent, ip_address)))
+    }
+}
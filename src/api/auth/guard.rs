@@ -0,0 +1,81 @@
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use log::debug;
+
+use crate::api::auth::access_token::JsonWebToken;
+use crate::api::error::ApiError;
+use crate::core::role::Permission;
+
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+pub struct ReadUsers;
+
+impl RequiredPermission for ReadUsers {
+    const PERMISSION: Permission = Permission::ReadUsers;
+}
+
+pub struct DeleteUsers;
+
+impl RequiredPermission for DeleteUsers {
+    const PERMISSION: Permission = Permission::DeleteUsers;
+}
+
+pub struct ManageRoles;
+
+impl RequiredPermission for ManageRoles {
+    const PERMISSION: Permission = Permission::ManageRoles;
+}
+
+pub struct ManageKeys;
+
+impl RequiredPermission for ManageKeys {
+    const PERMISSION: Permission = Permission::ManageKeys;
+}
+
+pub struct ReadMetrics;
+
+impl RequiredPermission for ReadMetrics {
+    const PERMISSION: Permission = Permission::ReadMetrics;
+}
+
+/// Extracts the access token and rejects the request with `403 Forbidden`
+/// unless one of its roles grants the permission `P`.
+pub struct RequirePermission<P: RequiredPermission> {
+    jwt: JsonWebToken,
+    permission: PhantomData<P>,
+}
+
+impl<P: RequiredPermission> RequirePermission<P> {
+    pub fn jwt(&self) -> &JsonWebToken {
+        &self.jwt
+    }
+}
+
+impl<P: RequiredPermission> FromRequest for RequirePermission<P> {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let jwt = match JsonWebToken::from_request(req, payload).into_inner() {
+            Ok(jwt) => jwt,
+            Err(err) => return ready(Err(err)),
+        };
+        if jwt.roles().iter().any(|role| role.has_permission(P::PERMISSION)) {
+            return ready(Ok(Self {
+                jwt,
+                permission: PhantomData,
+            }));
+        }
+        debug!(
+            "RequirePermission rejected username={:?} for permission={:?}",
+            jwt.username(),
+            P::PERMISSION
+        );
+        ready(Err(ApiError::Forbidden("insufficient permissions".to_owned())))
+    }
+}
@@ -6,23 +6,61 @@ use actix_web::{
 use log::debug;
 
 use crate::api::auth::access_token::JsonWebToken;
+use crate::api::auth::guard::{ManageKeys, RequirePermission};
 use crate::api::auth::refresh_token::RefreshToken;
+use crate::api::auth::signing_key::KeyStore;
 use crate::api::error::ApiError;
-use crate::business::auth::request::LoginUserRequest;
-use crate::business::auth::service::AuthService;
+use crate::api::mfa::response::MfaChallengeResponse;
+use crate::business::auth::request::{
+    ChangePasswordRequest, ForgotPasswordRequest, LoginUserRequest, MfaLoginRequest,
+    ResetPasswordRequest,
+};
+use crate::business::auth::service::{AuthService, LoginResult};
+use crate::core::token::ClientInfo;
+use crate::settings::Settings;
 
 pub async fn login(
     auth_service: Data<AuthService>,
+    key_store: Data<KeyStore>,
+    settings: Data<Settings>,
+    client_info: ClientInfo,
     json: Json<LoginUserRequest>,
 ) -> Result<HttpResponse, ApiError> {
     debug!("auth/handler.login() with inputs: {json:?}");
-    let user_dto = auth_service.login(json.into_inner()).await?;
+    let user_dto = match auth_service.login(json.into_inner(), client_info).await? {
+        LoginResult::Authenticated(user_dto) => user_dto,
+        LoginResult::MfaRequired(challenge) => {
+            return Ok(HttpResponse::Accepted().json(MfaChallengeResponse::new(challenge)))
+        }
+    };
     let new_refresh_token = RefreshToken::new(
         user_dto
             .latest_token()
             .expect("if no token had been created, auth_service would have failed"),
+        &settings.tokens.refresh_cookie_name,
     );
-    let access_token = JsonWebToken::new(user_dto.username());
+    let access_token = JsonWebToken::new(&key_store, &settings.jwt, user_dto.username(), user_dto.roles());
+    Ok(HttpResponse::Ok()
+        .cookie(new_refresh_token.cookie().clone())
+        .json(Json(access_token.key().to_owned())))
+}
+
+pub async fn login_mfa(
+    auth_service: Data<AuthService>,
+    key_store: Data<KeyStore>,
+    settings: Data<Settings>,
+    client_info: ClientInfo,
+    json: Json<MfaLoginRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!("auth/handler.login_mfa()");
+    let user_dto = auth_service.complete_mfa_login(json.into_inner(), client_info).await?;
+    let new_refresh_token = RefreshToken::new(
+        user_dto
+            .latest_token()
+            .expect("if no token had been created, auth_service would have failed"),
+        &settings.tokens.refresh_cookie_name,
+    );
+    let access_token = JsonWebToken::new(&key_store, &settings.jwt, user_dto.username(), user_dto.roles());
     Ok(HttpResponse::Ok()
         .cookie(new_refresh_token.cookie().clone())
         .json(Json(access_token.key().to_owned())))
@@ -30,16 +68,20 @@ pub async fn login(
 
 pub async fn refresh(
     auth_service: Data<AuthService>,
+    key_store: Data<KeyStore>,
+    settings: Data<Settings>,
+    client_info: ClientInfo,
     refresh_token: RefreshToken<'_>,
 ) -> Result<HttpResponse, ApiError> {
     debug!("auth/handler.refresh() with inputs: refresh_token={refresh_token:?}");
-    let user_dto = auth_service.refresh(refresh_token.key().as_ref()).await?;
+    let user_dto = auth_service.refresh(refresh_token.key(), client_info).await?;
     let new_refresh_token = RefreshToken::new(
         user_dto
             .latest_token()
             .expect("if no token had been created, auth_service would have failed"),
+        &settings.tokens.refresh_cookie_name,
     );
-    let access_token = JsonWebToken::new(user_dto.username());
+    let access_token = JsonWebToken::new(&key_store, &settings.jwt, user_dto.username(), user_dto.roles());
     Ok(HttpResponse::Ok()
         .cookie(new_refresh_token.cookie().clone())
         .json(Json(access_token.key().to_owned())))
@@ -50,6 +92,52 @@ pub async fn logout(
     refresh_token: RefreshToken<'_>,
 ) -> Result<HttpResponse, ApiError> {
     debug!("auth/handler.logout() with inputs: refresh_token={refresh_token:?}");
-    auth_service.logout(refresh_token.key().as_ref()).await?;
+    auth_service.logout(refresh_token.key()).await?;
     Ok(HttpResponse::Ok().finish())
 }
+
+pub async fn jwks(key_store: Data<KeyStore>) -> HttpResponse {
+    debug!("auth/handler.jwks()");
+    HttpResponse::Ok().json(key_store.jwks())
+}
+
+pub async fn rotate_keys(
+    key_store: Data<KeyStore>,
+    guard: RequirePermission<ManageKeys>,
+) -> Result<HttpResponse, ApiError> {
+    debug!("auth/handler.rotate_keys() with inputs: username={:?}", guard.jwt().username());
+    let kid = key_store.rotate()?;
+    Ok(HttpResponse::Ok().json(Json(kid)))
+}
+
+pub async fn change_password(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+    refresh_token: Option<RefreshToken<'_>>,
+    json: Json<ChangePasswordRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!("auth/handler.change_password() with inputs: username={:?}", jwt.username());
+    let current_refresh_token = refresh_token.as_ref().map(RefreshToken::key);
+    auth_service
+        .change_password(jwt.username(), json.into_inner(), current_refresh_token)
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn forgot_password(
+    auth_service: Data<AuthService>,
+    json: Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!("auth/handler.forgot_password() with inputs: json={:?}", json);
+    auth_service.forgot_password(json.into_inner()).await?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+pub async fn reset_password(
+    auth_service: Data<AuthService>,
+    json: Json<ResetPasswordRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!("auth/handler.reset_password()");
+    auth_service.reset_password(json.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
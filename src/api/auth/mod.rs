@@ -1,3 +1,6 @@
 pub mod handler;
 pub mod access_token;
+pub mod client_info;
+pub mod guard;
 pub mod refresh_token;
+pub mod signing_key;
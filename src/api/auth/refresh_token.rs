@@ -1,14 +1,15 @@
+use crate::api::error::ApiError;
 use crate::core::error::AuthenticationError;
 use crate::core::token::TokenDto;
+use crate::settings::Settings;
 use actix_web::cookie::time::Duration;
 use actix_web::cookie::Cookie;
 use actix_web::dev::Payload;
+use actix_web::web::Data;
 use actix_web::{FromRequest, HttpRequest};
-use std::borrow::Cow;
 use std::future::{ready, Ready};
 use std::time::SystemTime;
 
-const RT_COOKIE_NAME: &str = "refresh-token";
 const RT_COOKIE_HTTP_ONLY: bool = true;
 
 #[derive(Debug)]
@@ -17,37 +18,40 @@ pub struct RefreshToken<'a> {
 }
 
 impl<'a> RefreshToken<'a> {
-    pub fn new(token_dto: &'a TokenDto) -> Self {
-        let key = urlencoding::encode(token_dto.key());
+    pub fn new(token_dto: &'a TokenDto, cookie_name: &'a str) -> Self {
+        let key = token_dto
+            .key()
+            .expect("refresh tokens are only issued right after creation");
         let ttl = token_dto
             .expire_at()
             .duration_since(SystemTime::now())
             .expect("System clock may have gone backwards");
         let max_age = Duration::try_from(ttl).expect("ttl is expected to be valid");
 
-        let mut cookie = Cookie::new(RT_COOKIE_NAME, key);
+        let mut cookie = Cookie::new(cookie_name, key);
         cookie.set_http_only(RT_COOKIE_HTTP_ONLY);
         cookie.set_max_age(max_age);
         Self { cookie }
     }
-    pub fn cookie(&self) -> &Cookie {
+    pub fn cookie(&self) -> &Cookie<'a> {
         &self.cookie
     }
-    pub fn key(&self) -> Cow<str> {
-        urlencoding::decode(self.cookie.value()).unwrap()
+    pub fn key(&self) -> &str {
+        self.cookie.value()
     }
 }
 
 impl<'a> FromRequest for RefreshToken<'a> {
-    type Error = AuthenticationError;
+    type Error = ApiError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
-        if let Some(cookie) = request.cookie("refresh-token") {
+        let settings = request
+            .app_data::<Data<Settings>>()
+            .expect("Settings is registered as app data");
+        if let Some(cookie) = request.cookie(&settings.tokens.refresh_cookie_name) {
             return ready(Ok(Self { cookie }));
         }
-        ready(Err(AuthenticationError::new(
-            "could not read refresh token",
-        )))
+        ready(Err(AuthenticationError::new("could not read refresh token").into()))
     }
 }
@@ -0,0 +1,287 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use log::{debug, info, warn};
+use ring::rand::SystemRandom;
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use uuid::Uuid;
+
+const PEM_EXTENSION: &str = "pem";
+
+#[derive(Debug)]
+pub struct KeyError {
+    message: String,
+}
+
+impl KeyError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+    created_at: SystemTime,
+}
+
+impl SigningKey {
+    /// Parses a PKCS#8 (Ed25519, P-256, RSA) or PKCS#1 (RSA) private key and
+    /// derives the public half needed for verification and the JWKS endpoint.
+    fn from_pem(kid: &str, bytes: &[u8], created_at: SystemTime) -> Result<Self, KeyError> {
+        let invalid = |err: &dyn ToString| KeyError::new(format!("key {kid}: {}", err.to_string()));
+        let pem = pem::parse(bytes).map_err(|err| invalid(&err))?;
+        let der = pem.contents();
+
+        let (algorithm, encoding_key, parameters) = match pem.tag() {
+            "RSA PRIVATE KEY" => {
+                let key_pair = RsaKeyPair::from_der(der).map_err(|err| invalid(&err))?;
+                let encoding_key = EncodingKey::from_rsa_pem(bytes).map_err(|err| invalid(&err))?;
+                (Algorithm::RS256, encoding_key, rsa_parameters(&key_pair))
+            }
+            "PRIVATE KEY" => {
+                if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                    let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+                    });
+                    (Algorithm::EdDSA, EncodingKey::from_ed_der(der), parameters)
+                } else if let Ok(key_pair) = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    der,
+                    &SystemRandom::new(),
+                ) {
+                    // uncompressed point: 0x04 || x || y
+                    let point = key_pair.public_key().as_ref();
+                    let parameters = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                        y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+                    });
+                    (Algorithm::ES256, EncodingKey::from_ec_der(der), parameters)
+                } else {
+                    let key_pair = RsaKeyPair::from_pkcs8(der).map_err(|err| invalid(&err))?;
+                    let encoding_key =
+                        EncodingKey::from_rsa_pem(bytes).map_err(|err| invalid(&err))?;
+                    (Algorithm::RS256, encoding_key, rsa_parameters(&key_pair))
+                }
+            }
+            tag => return Err(invalid(&format!("unsupported PEM block {tag:?}"))),
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(algorithm)),
+                key_id: Some(kid.to_owned()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+        let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|err| invalid(&err))?;
+
+        Ok(Self {
+            kid: kid.to_owned(),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk,
+            created_at,
+        })
+    }
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+/// Holds every key found in the key directory. The most recently created key
+/// signs new tokens, all others remain valid for verification until they are
+/// pruned after a rotation.
+pub struct KeyStore {
+    directory: PathBuf,
+    algorithm: Algorithm,
+    retention: Duration,
+    keys: RwLock<Vec<Arc<SigningKey>>>,
+}
+
+impl KeyStore {
+    pub fn load(directory: &Path, algorithm: Algorithm, retention: Duration) -> Result<Self, KeyError> {
+        let key_store = Self {
+            directory: directory.to_owned(),
+            algorithm,
+            retention,
+            keys: RwLock::new(Vec::new()),
+        };
+        key_store.reload()?;
+        if key_store.keys.read().unwrap().is_empty() {
+            info!("KeyStore found no keys in {:?}, generating one", directory);
+            key_store.rotate()?;
+        }
+        Ok(key_store)
+    }
+    pub fn reload(&self) -> Result<(), KeyError> {
+        let mut keys = Vec::new();
+        for path in self.key_files()? {
+            let kid = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| KeyError::new(format!("invalid key file name {path:?}")))?;
+            let bytes = fs::read(&path).map_err(|err| KeyError::new(format!("{path:?}: {err}")))?;
+            let created_at = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|err| KeyError::new(format!("{path:?}: {err}")))?;
+            keys.push(Arc::new(SigningKey::from_pem(kid, &bytes, created_at)?));
+        }
+        keys.sort_by_key(|key| key.created_at);
+        debug!(
+            "KeyStore.reload() loaded kids={:?}",
+            keys.iter().map(|key| key.kid()).collect::<Vec<_>>()
+        );
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+    /// Generates a new signing key, writes it to the key directory and prunes
+    /// keys that were superseded longer than the retention period ago.
+    pub fn rotate(&self) -> Result<String, KeyError> {
+        let rng = SystemRandom::new();
+        let document = match self.algorithm {
+            Algorithm::EdDSA => Ed25519KeyPair::generate_pkcs8(&rng),
+            Algorithm::ES256 => EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng),
+            algorithm => {
+                return Err(KeyError::new(format!(
+                    "cannot generate keys for {algorithm:?}, provide them as PEM files instead"
+                )))
+            }
+        }
+        .map_err(|err| KeyError::new(err.to_string()))?;
+
+        let kid = Uuid::now_v7().to_string();
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", document.as_ref()));
+        fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(self.key_path(&kid), pem))
+            .map_err(|err| KeyError::new(format!("{:?}: {err}", self.directory)))?;
+        info!("KeyStore rotated to kid={kid}");
+
+        self.reload()?;
+        self.prune()?;
+        Ok(kid)
+    }
+    pub fn signing_key(&self) -> Arc<SigningKey> {
+        self.keys
+            .read()
+            .unwrap()
+            .last()
+            .cloned()
+            .expect("key store always holds at least one key")
+    }
+    pub fn verification_key(&self, kid: &str) -> Option<Arc<SigningKey>> {
+        self.keys
+            .read()
+            .unwrap()
+            .iter()
+            .find(|key| key.kid() == kid)
+            .cloned()
+    }
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.read().unwrap().iter().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+    pub fn spawn_rotation(self: Arc<Self>, interval: Duration) {
+        actix_web::rt::spawn(async move {
+            let mut ticker = actix_web::rt::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.rotate() {
+                    warn!("KeyStore scheduled rotation failed: {err}");
+                }
+            }
+        });
+    }
+    /// A key is superseded when its successor was created; tokens it signed
+    /// live at most `retention` longer.
+    fn prune(&self) -> Result<(), KeyError> {
+        let now = SystemTime::now();
+        let keys = self.keys.read().unwrap().clone();
+        let expired = keys
+            .windows(2)
+            .filter(|pair| pair[1].created_at + self.retention < now)
+            .map(|pair| pair[0].kid().to_owned())
+            .collect::<Vec<_>>();
+        for kid in &expired {
+            info!("KeyStore pruning kid={kid}");
+            fs::remove_file(self.key_path(kid))
+                .map_err(|err| KeyError::new(format!("{kid}: {err}")))?;
+        }
+        if !expired.is_empty() {
+            self.reload()?;
+        }
+        Ok(())
+    }
+    fn key_files(&self) -> Result<Vec<PathBuf>, KeyError> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = fs::read_dir(&self.directory)
+            .map_err(|err| KeyError::new(format!("{:?}: {err}", self.directory)))?;
+        Ok(entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == PEM_EXTENSION))
+            .collect())
+    }
+    fn key_path(&self, kid: &str) -> PathBuf {
+        self.directory.join(format!("{kid}.{PEM_EXTENSION}"))
+    }
+}
+
+fn rsa_parameters(key_pair: &RsaKeyPair) -> AlgorithmParameters {
+    let components = PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+    AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: URL_SAFE_NO_PAD.encode(components.n),
+        e: URL_SAFE_NO_PAD.encode(components.e),
+    })
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        _ => KeyAlgorithm::RS256,
+    }
+}
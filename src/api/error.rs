@@ -1,32 +1,176 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use log::{debug, error};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::api::auth::signing_key::KeyError;
 use crate::business::error::BusinessError;
+use crate::core::error::{AuthenticationError, FieldError, ThrottledError, ValidationError};
+
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+const PROBLEM_TYPE_PREFIX: &str = "urn:abcd:problem:";
 
 #[derive(Debug)]
-pub struct ApiError {
-    message: String,
+pub enum ApiError {
+    /// The request could not be read, e.g. malformed JSON or path segments.
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Validation(ValidationError),
+    Throttled(ThrottledError),
+    RateLimited(Duration),
+    /// Logged with its details, clients only learn that something went wrong.
+    Internal(String),
 }
 
-impl std::fmt::Display for ApiError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+impl ApiError {
+    /// Stable machine-readable identifier, also the last segment of the problem type.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Throttled(ThrottledError::AccountLocked { .. }) => "account_locked",
+            ApiError::Throttled(ThrottledError::TooManyAttempts { .. }) => "too_many_attempts",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::Throttled(error) => Some(error.retry_after()),
+            ApiError::RateLimited(retry_after) => Some(*retry_after),
+            _ => None,
+        }
+    }
+    /// Rejects request bodies the `Json` extractor could not read.
+    pub fn json_error_handler(error: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+        debug!("ApiError.json_error_handler() with inputs: error={:?}", error);
+        ApiError::BadRequest(error.to_string()).into()
+    }
+    /// Rejects path segments the `Path` extractor could not parse.
+    pub fn path_error_handler(error: PathError, _: &HttpRequest) -> actix_web::Error {
+        debug!("ApiError.path_error_handler() with inputs: error={:?}", error);
+        ApiError::BadRequest(error.to_string()).into()
+    }
+    /// Rejects query strings the `Query` extractor could not parse.
+    pub fn query_error_handler(error: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+        debug!("ApiError.query_error_handler() with inputs: error={:?}", error);
+        ApiError::BadRequest(error.to_string()).into()
     }
 }
 
-impl actix_web::ResponseError for ApiError {
-    fn error_response(&self) -> actix_web::HttpResponse {
-        actix_web::HttpResponse::BadRequest().json(&self.message)
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::Internal(message) => write!(f, "{message}"),
+            ApiError::Validation(error) => write!(f, "{error}"),
+            ApiError::Throttled(error) => write!(f, "{error}"),
+            ApiError::RateLimited(_) => write!(f, "rate limit exceeded"),
+        }
     }
 }
 
-impl From<&dyn ToString> for ApiError {
-    fn from(error: &dyn ToString) -> Self {
-        ApiError {
-            message: error.to_string(),
+/// RFC 9457 problem details.
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    /// Identifies this occurrence, internal errors are logged with it.
+    instance: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<&'a [FieldError]>,
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Throttled(ThrottledError::AccountLocked { .. }) => StatusCode::LOCKED,
+            ApiError::Throttled(ThrottledError::TooManyAttempts { .. }) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let instance = format!("urn:uuid:{}", Uuid::now_v7());
+        let detail = match self {
+            ApiError::Internal(message) => {
+                error!("internal error instance={instance}: {message}");
+                "an unexpected error occurred".to_owned()
+            }
+            error => error.to_string(),
+        };
+        let problem = Problem {
+            problem_type: format!("{PROBLEM_TYPE_PREFIX}{}", self.code()),
+            title: status.canonical_reason().unwrap_or_default(),
+            status: status.as_u16(),
+            detail,
+            instance,
+            code: self.code(),
+            errors: match self {
+                ApiError::Validation(error) => Some(error.errors()),
+                _ => None,
+            },
+        };
+        let mut response = HttpResponse::build(status);
+        response.insert_header((CONTENT_TYPE, PROBLEM_CONTENT_TYPE));
+        if let Some(retry_after) = self.retry_after() {
+            // Retry-After is given in whole seconds, round up so clients never retry too early
+            let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response.insert_header((RETRY_AFTER, retry_after.to_string()));
         }
+        response.json(problem)
     }
 }
 
 impl From<BusinessError> for ApiError {
     fn from(error: BusinessError) -> Self {
-        ApiError::from(&error as &dyn ToString)
+        match error {
+            BusinessError::NotFound(message) => ApiError::NotFound(message),
+            BusinessError::Conflict(message) => ApiError::Conflict(message),
+            BusinessError::Unauthorized(message) => ApiError::Unauthorized(message),
+            BusinessError::Validation(error) => ApiError::Validation(error),
+            BusinessError::Throttled(error) => ApiError::Throttled(error),
+            BusinessError::Internal(message) => ApiError::Internal(message),
+        }
+    }
+}
+
+impl From<AuthenticationError> for ApiError {
+    fn from(error: AuthenticationError) -> Self {
+        ApiError::Unauthorized(error.message().to_owned())
+    }
+}
+
+impl From<KeyError> for ApiError {
+    fn from(error: KeyError) -> Self {
+        ApiError::Internal(error.to_string())
     }
 }
@@ -0,0 +1,25 @@
+use actix_web::HttpResponse;
+use log::debug;
+
+use crate::api::auth::guard::{ReadMetrics, RequirePermission};
+use crate::driver::database::client_adapter::StatementCacheMetrics;
+
+/// Counters in the Prometheus text exposition format. They tell how busy
+/// the service is, so the scraper needs an access token of an admin.
+pub async fn index(guard: RequirePermission<ReadMetrics>) -> HttpResponse {
+    debug!("metrics/handler.index() with inputs: username={:?}", guard.jwt().username());
+    let statement_cache = StatementCacheMetrics::global();
+    let body = format!(
+        "# HELP statement_cache_hits_total Statements served from a connection's statement cache.\n\
+         # TYPE statement_cache_hits_total counter\n\
+         statement_cache_hits_total {}\n\
+         # HELP statement_cache_misses_total Statements prepared on Postgres and added to the cache.\n\
+         # TYPE statement_cache_misses_total counter\n\
+         statement_cache_misses_total {}\n",
+        statement_cache.hits(),
+        statement_cache.misses(),
+    );
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+}
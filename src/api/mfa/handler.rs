@@ -0,0 +1,30 @@
+use actix_web::{
+    Result,
+    web::{Data, Json},
+};
+use log::debug;
+
+use crate::api::auth::access_token::JsonWebToken;
+use crate::api::error::ApiError;
+use crate::api::mfa::response::{MfaEnrollmentResponse, RecoveryCodesResponse};
+use crate::business::auth::request::ConfirmMfaRequest;
+use crate::business::auth::service::AuthService;
+
+pub async fn enroll(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+) -> Result<Json<MfaEnrollmentResponse>, ApiError> {
+    debug!("mfa/handler.enroll() with inputs: username={:?}", jwt.username());
+    let enrollment = auth_service.enroll_mfa(jwt.username()).await?;
+    Ok(Json(MfaEnrollmentResponse::from(enrollment)))
+}
+
+pub async fn confirm(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+    json: Json<ConfirmMfaRequest>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    debug!("mfa/handler.confirm() with inputs: username={:?}", jwt.username());
+    let recovery_codes = auth_service.confirm_mfa(jwt.username(), json.into_inner()).await?;
+    Ok(Json(RecoveryCodesResponse::from(recovery_codes)))
+}
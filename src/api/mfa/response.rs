@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct MfaChallengeResponse {
+    mfa_required: bool,
+    challenge: String,
+}
+
+impl MfaChallengeResponse {
+    pub fn new(challenge: String) -> Self {
+        Self {
+            mfa_required: true,
+            challenge,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct MfaEnrollmentResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+impl From<(String, String)> for MfaEnrollmentResponse {
+    fn from((secret, otpauth_uri): (String, String)) -> Self {
+        Self { secret, otpauth_uri }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+impl From<Vec<String>> for RecoveryCodesResponse {
+    fn from(recovery_codes: Vec<String>) -> Self {
+        Self { recovery_codes }
+    }
+}
@@ -0,0 +1,149 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{Error, ResponseError};
+use log::debug;
+
+use crate::api::auth::access_token::JsonWebToken;
+use crate::api::error::ApiError;
+use crate::api::middleware::rate_limit_store::{Quota, RateLimitDecision, RateLimitStore};
+
+const RATE_LIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// What a bucket is shared by.
+#[derive(Debug, Clone, Copy)]
+pub enum RateLimitKey {
+    ClientIp,
+    /// The subject of a valid access token, anonymous requests fall back to
+    /// their client IP.
+    JwtSubject,
+    /// A single bucket for every client of the route.
+    Route,
+}
+
+/// Token bucket rate limiting for the scope or resource it wraps. `name`
+/// separates the buckets of different limits that share a store.
+#[derive(Clone)]
+pub struct RateLimit {
+    name: &'static str,
+    quota: Quota,
+    key: RateLimitKey,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimit {
+    pub fn new(
+        name: &'static str,
+        quota: Quota,
+        key: RateLimitKey,
+        store: Arc<dyn RateLimitStore>,
+    ) -> Self {
+        Self {
+            name,
+            quota,
+            key,
+            store,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            rate_limit: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    rate_limit: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let rate_limit = self.rate_limit.clone();
+        Box::pin(async move {
+            let key = format!("{}:{}", rate_limit.name, bucket_key(rate_limit.key, &mut req).await);
+            let decision = rate_limit.store.acquire(&key, &rate_limit.quota).await;
+            if !decision.allowed {
+                debug!("RateLimit rejected request for key={:?}", key);
+                let mut response = ApiError::RateLimited(decision.retry_after).error_response();
+                insert_headers(response.headers_mut(), &rate_limit.quota, &decision);
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+            let mut res = service.call(req).await?;
+            insert_headers(res.headers_mut(), &rate_limit.quota, &decision);
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+async fn bucket_key(key: RateLimitKey, req: &mut ServiceRequest) -> String {
+    let client_ip = |req: &ServiceRequest| {
+        req.peer_addr()
+            .map(|address| address.ip().to_string())
+            .unwrap_or_default()
+    };
+    match key {
+        RateLimitKey::ClientIp => client_ip(req),
+        RateLimitKey::JwtSubject => match req.extract::<JsonWebToken>().await {
+            Ok(jwt) => format!("sub:{}", jwt.username()),
+            Err(_) => client_ip(req),
+        },
+        RateLimitKey::Route => req.match_pattern().unwrap_or_else(|| req.path().to_owned()),
+    }
+}
+
+/// When limits are nested, the headers describe whichever has the fewest
+/// requests left.
+fn insert_headers(headers: &mut HeaderMap, quota: &Quota, decision: &RateLimitDecision) {
+    let remaining = headers
+        .get(&RATE_LIMIT_REMAINING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok());
+    if remaining.is_some_and(|remaining| remaining <= decision.remaining) {
+        return;
+    }
+    let policy = format!("{};w={}", quota.capacity(), whole_secs(quota.period()));
+    headers.insert(RATE_LIMIT_POLICY, HeaderValue::from_str(&policy).unwrap());
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(quota.capacity()));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(whole_secs(decision.reset)));
+}
+
+/// Rounds up so clients never retry too early.
+fn whole_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use log::debug;
+
+const SHARD_COUNT: usize = 16;
+const MAX_KEYS_PER_SHARD: usize = 4096;
+
+/// Token bucket of `capacity` requests that refills completely within `period`.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    capacity: u32,
+    period: Duration,
+}
+
+impl Quota {
+    /// Fails unless both the capacity and the period are positive.
+    pub fn new(capacity: u32, period: Duration) -> Result<Self, String> {
+        if capacity == 0 {
+            return Err("quota capacity must be positive".to_owned());
+        }
+        if period.is_zero() {
+            return Err("quota period must be positive".to_owned());
+        }
+        Ok(Self { capacity, period })
+    }
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+    fn tokens_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+    /// Time until the next request would be allowed.
+    pub retry_after: Duration,
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Takes one token from the bucket identified by `key`.
+    async fn acquire(&self, key: &str, quota: &Quota) -> RateLimitDecision;
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// From here on the bucket is indistinguishable from a new one.
+    full_at: Instant,
+}
+
+#[derive(Debug)]
+struct Shard {
+    buckets: HashMap<String, Bucket>,
+    /// Earliest `full_at` seen, nothing can be evicted before it.
+    next_sweep: Instant,
+}
+
+impl Shard {
+    /// Drops the buckets that have refilled completely, at most once per
+    /// refill deadline, so a shard full of live buckets is not rescanned on
+    /// every request.
+    fn sweep(&mut self, now: Instant, max_keys: usize) {
+        if self.buckets.len() < max_keys || now < self.next_sweep {
+            return;
+        }
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+        self.next_sweep = self.buckets.values().map(|bucket| bucket.full_at).min().unwrap_or(now);
+    }
+    /// Makes room for a new key in a shard full of refilling buckets by
+    /// dropping the one closest to full, it loses the least.
+    fn evict_soonest_full(&mut self) {
+        let soonest_full = self
+            .buckets
+            .iter()
+            .min_by_key(|(_, bucket)| bucket.full_at)
+            .map(|(key, _)| key.to_owned());
+        if let Some(key) = soonest_full {
+            debug!("rate limit store is full, evicting bucket of key={:?}", key);
+            self.buckets.remove(&key);
+        }
+    }
+}
+
+/// Keeps buckets in memory, spread over several independently locked shards
+/// to reduce contention between workers. Once a shard is full of buckets that
+/// are still refilling, a new key replaces the bucket that refills soonest, so
+/// flooding the store with keys cannot lock out new clients.
+#[derive(Debug)]
+pub struct ShardedMemoryStore {
+    shards: Vec<Mutex<Shard>>,
+    max_keys_per_shard: usize,
+    hasher: RandomState,
+}
+
+impl ShardedMemoryStore {
+    pub fn new() -> Self {
+        Self::with_max_keys(SHARD_COUNT * MAX_KEYS_PER_SHARD)
+    }
+    /// Bounds the number of buckets kept, `max_keys` is spread evenly over
+    /// the shards.
+    pub fn with_max_keys(max_keys: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            shards: (0..SHARD_COUNT)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        buckets: HashMap::new(),
+                        next_sweep: now,
+                    })
+                })
+                .collect(),
+            max_keys_per_shard: max_keys.div_ceil(SHARD_COUNT).max(1),
+            hasher: RandomState::new(),
+        }
+    }
+    fn shard(&self, key: &str) -> &Mutex<Shard> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+impl Default for ShardedMemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for ShardedMemoryStore {
+    async fn acquire(&self, key: &str, quota: &Quota) -> RateLimitDecision {
+        let now = Instant::now();
+        let capacity = quota.capacity() as f64;
+        let rate = quota.tokens_per_sec();
+        let mut guard = self.shard(key).lock().unwrap();
+        let shard = &mut *guard;
+        shard.sweep(now, self.max_keys_per_shard);
+        if shard.buckets.len() >= self.max_keys_per_shard && !shard.buckets.contains_key(key) {
+            shard.evict_soonest_full();
+        }
+        let bucket = shard.buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            full_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let reset = Duration::from_secs_f64((capacity - bucket.tokens) / rate);
+        bucket.full_at = now + reset;
+        let decision = RateLimitDecision {
+            allowed,
+            remaining: bucket.tokens.floor() as u32,
+            reset,
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / rate),
+        };
+        shard.next_sweep = shard.next_sweep.min(bucket.full_at);
+        decision
+    }
+}
@@ -1,3 +1,9 @@
+pub mod app_builder;
 pub mod auth;
 pub mod error;
+pub mod metrics;
+pub mod mfa;
+pub mod routes;
+pub mod middleware;
+pub mod session;
 pub mod user;
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::{
+    delete, get, post, resource, scope, JsonConfig, PathConfig, QueryConfig, ServiceConfig,
+};
+
+use crate::api::auth::handler as auth_handler;
+use crate::api::error::ApiError;
+use crate::api::metrics::handler as metrics_handler;
+use crate::api::middleware::rate_limit::{RateLimit, RateLimitKey};
+use crate::api::middleware::rate_limit_store::{Quota, RateLimitStore};
+use crate::api::mfa::handler as mfa_handler;
+use crate::api::session::handler as session_handler;
+use crate::api::user::handler as user_handler;
+
+/// Registers every route of the service. `Settings`, `UserService`,
+/// `AuthService` and `KeyStore` must be available as app data, see `AppBuilder`.
+pub fn configure(cfg: &mut ServiceConfig, rate_limit_store: Arc<dyn RateLimitStore>) {
+    let minute = Duration::from_secs(60);
+    let quota = |capacity, period| Quota::new(capacity, period).expect("route quotas are positive");
+    cfg.app_data(JsonConfig::default().error_handler(ApiError::json_error_handler))
+        .app_data(PathConfig::default().error_handler(ApiError::path_error_handler))
+        .app_data(QueryConfig::default().error_handler(ApiError::query_error_handler))
+        .route("/.well-known/jwks.json", get().to(auth_handler::jwks))
+        .route("/metrics", get().to(metrics_handler::index))
+        .service(resource("/keys/rotate")
+            .wrap(RateLimit::new("keys", quota(1, minute), RateLimitKey::JwtSubject, rate_limit_store.clone()))
+            .route(post().to(auth_handler::rotate_keys)))
+        .route("/login", post().to(auth_handler::login))
+        .route("/login/mfa", post().to(auth_handler::login_mfa))
+        .service(resource("/refresh")
+            .wrap(RateLimit::new("refresh", quota(10, minute), RateLimitKey::ClientIp, rate_limit_store.clone()))
+            .route(get().to(auth_handler::refresh)))
+        .route("/logout", post().to(auth_handler::logout))
+        .route("/logout-all", post().to(session_handler::logout_all))
+        .service(scope("/mfa")
+            .route("/enroll", post().to(mfa_handler::enroll))
+            .route("/confirm", post().to(mfa_handler::confirm)))
+        .service(scope("/password")
+            .route("/change", post().to(auth_handler::change_password))
+            .route("/forgot", post().to(auth_handler::forgot_password))
+            .route("/reset", post().to(auth_handler::reset_password)))
+        .service(scope("/sessions")
+            .route("", get().to(session_handler::index))
+            .route("/{id}", delete().to(session_handler::delete)))
+        .service(scope("/users")
+            .wrap(RateLimit::new("users", quota(60, minute), RateLimitKey::JwtSubject, rate_limit_store.clone()))
+            .service(resource("/register")
+                // every registration hashes a password, this caps the total on top of each client
+                .wrap(RateLimit::new("registrations", quota(100, minute), RateLimitKey::Route, rate_limit_store.clone()))
+                .wrap(RateLimit::new("register", quota(5, 60 * minute), RateLimitKey::ClientIp, rate_limit_store))
+                .route(post().to(user_handler::register)))
+            .route("", get().to(user_handler::index))
+            .route("/protected", get().to(user_handler::protected_index))
+            .route("/{id}", get().to(user_handler::show))
+            .route("/delete", post().to(user_handler::delete))
+            .route("/roles", post().to(user_handler::grant_role)));
+}
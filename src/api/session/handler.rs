@@ -0,0 +1,43 @@
+use actix_web::{
+    HttpResponse,
+    Result, web::{Data, Json, Path},
+};
+use log::debug;
+use uuid::Uuid;
+
+use crate::api::auth::access_token::JsonWebToken;
+use crate::api::error::ApiError;
+use crate::api::session::response::SessionResponse;
+use crate::business::auth::service::AuthService;
+
+pub async fn index(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+) -> Result<Json<Vec<SessionResponse>>, ApiError> {
+    debug!("session/handler.index() with inputs: username={:?}", jwt.username());
+    let sessions = auth_service.sessions(jwt.username()).await?;
+    Ok(Json(sessions.iter().map(SessionResponse::from).collect()))
+}
+
+pub async fn delete(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+    params: Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    debug!(
+        "session/handler.delete() with inputs: username={:?}, params={:?}",
+        jwt.username(),
+        params
+    );
+    auth_service.revoke_session(jwt.username(), &params.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn logout_all(
+    auth_service: Data<AuthService>,
+    jwt: JsonWebToken,
+) -> Result<HttpResponse, ApiError> {
+    debug!("session/handler.logout_all() with inputs: username={:?}", jwt.username());
+    auth_service.logout_all(jwt.username()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
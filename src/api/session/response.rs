@@ -0,0 +1,34 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::core::token::TokenDto;
+
+/// Times are seconds since the Unix epoch.
+#[derive(Serialize, Debug)]
+pub struct SessionResponse {
+    id: Uuid,
+    created_at: u64,
+    last_used_at: u64,
+    expire_at: u64,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl From<&TokenDto> for SessionResponse {
+    fn from(token_dto: &TokenDto) -> Self {
+        Self {
+            id: *token_dto.id(),
+            created_at: epoch_secs(token_dto.created_at()),
+            last_used_at: epoch_secs(token_dto.last_used_at()),
+            expire_at: epoch_secs(token_dto.expire_at()),
+            user_agent: token_dto.user_agent().map(str::to_owned),
+            ip_address: token_dto.ip_address().map(str::to_owned),
+        }
+    }
+}
+
+fn epoch_secs(time: &SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
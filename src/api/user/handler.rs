@@ -1,39 +1,64 @@
+use actix_web::http::header::LINK;
 use actix_web::{
-    HttpResponse,
-    Result, web::{Data, Json, Path},
+    HttpRequest, HttpResponse,
+    Result, web::{Data, Json, Path, Query},
 };
 use log::debug;
 use uuid::Uuid;
 
-use crate::api::auth::access_token::JsonWebToken;
+use crate::api::auth::guard::{DeleteUsers, ManageRoles, ReadUsers, RequirePermission};
 use crate::api::error::ApiError;
+use crate::api::user::response::{UserPageResponse, UserResponse};
 use crate::business::user::request::DeleteUserRequest;
+use crate::business::user::request::ListUsersRequest;
+use crate::business::user::request::GrantRoleRequest;
 use crate::business::user::request::RegisterUserRequest;
 use crate::business::user::service::UserService;
+use crate::core::page::Page;
 use crate::core::user::UserDto;
 
-pub async fn index(user_service: Data<UserService>) -> Result<Json<Vec<UserDto>>, ApiError> {
-    debug!("user/handler.index()");
-    let list_of_user = user_service.index().await?;
-    Ok(Json(list_of_user))
+pub async fn index(
+    user_service: Data<UserService>,
+    guard: RequirePermission<ReadUsers>,
+    request: HttpRequest,
+    query: Query<ListUsersRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!(
+        "user/handler.index() with inputs: username={:?}, query={:?}",
+        guard.jwt().username(),
+        query
+    );
+    let page_of_user = user_service.index(&query).await?;
+    Ok(page_response(&request, &query, &page_of_user))
 }
 
 pub async fn protected_index(
     user_service: Data<UserService>,
-    jwt: JsonWebToken,
-) -> Result<Json<Vec<UserDto>>, ApiError> {
-    debug!("user/handler.protected_index() with inputs: username={:?}", jwt.username());
-    let list_of_user = user_service.index().await?;
-    Ok(Json(list_of_user))
+    guard: RequirePermission<ReadUsers>,
+    request: HttpRequest,
+    query: Query<ListUsersRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!(
+        "user/handler.protected_index() with inputs: username={:?}, query={:?}",
+        guard.jwt().username(),
+        query
+    );
+    let page_of_user = user_service.index(&query).await?;
+    Ok(page_response(&request, &query, &page_of_user))
 }
 
 pub async fn show(
     user_service: Data<UserService>,
+    guard: RequirePermission<ReadUsers>,
     params: Path<Uuid>,
-) -> Result<Json<Option<UserDto>>, ApiError> {
-    debug!("user/handler.show() with inputs: params={:?}", params);
-    let option = user_service.show(params.into_inner()).await?;
-    Ok(Json(option))
+) -> Result<Json<UserResponse>, ApiError> {
+    debug!(
+        "user/handler.show() with inputs: username={:?}, params={:?}",
+        guard.jwt().username(),
+        params
+    );
+    let user_dto = user_service.show(params.into_inner()).await?;
+    Ok(Json(UserResponse::from(&user_dto)))
 }
 
 pub async fn register(
@@ -47,9 +72,47 @@ pub async fn register(
 
 pub async fn delete(
     user_service: Data<UserService>,
+    guard: RequirePermission<DeleteUsers>,
     json: Json<DeleteUserRequest>,
 ) -> Result<HttpResponse, ApiError> {
-    debug!("user/handler.delete() with inputs: json={:?}", json);
+    debug!(
+        "user/handler.delete() with inputs: username={:?}, json={:?}",
+        guard.jwt().username(),
+        json
+    );
     user_service.delete(json.into_inner()).await?;
     Ok(HttpResponse::NoContent().finish())
 }
+
+pub async fn grant_role(
+    user_service: Data<UserService>,
+    guard: RequirePermission<ManageRoles>,
+    json: Json<GrantRoleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    debug!(
+        "user/handler.grant_role() with inputs: username={:?}, json={:?}",
+        guard.jwt().username(),
+        json
+    );
+    user_service.grant_role(json.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Wraps the page in its envelope and, when more follow, points to the next
+/// one with an RFC 8288 `Link` header that keeps the filter and sort order.
+fn page_response(request: &HttpRequest, query: &ListUsersRequest, page: &Page<UserDto>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(cursor) = page.next_cursor() {
+        let mut next = format!(
+            "{}?limit={}&order={}&cursor={cursor}",
+            request.path(),
+            query.limit(),
+            query.order().as_str()
+        );
+        if let Some(prefix) = query.username_prefix() {
+            next.push_str(&format!("&username_prefix={}", urlencoding::encode(prefix)));
+        }
+        response.insert_header((LINK, format!("<{next}>; rel=\"next\"")));
+    }
+    response.json(UserPageResponse::from(page))
+}
@@ -0,0 +1,40 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::core::page::Page;
+use crate::core::user::UserDto;
+
+/// Public representation of a user, never carries credentials or tokens.
+#[derive(Serialize, Debug)]
+pub struct UserResponse {
+    id: Uuid,
+    username: String,
+    /// Seconds since the Unix epoch.
+    created_at: Option<u64>,
+}
+
+impl From<&UserDto> for UserResponse {
+    fn from(user_dto: &UserDto) -> Self {
+        Self {
+            id: *user_dto.id(),
+            username: user_dto.username().to_owned(),
+            // ids are UUIDv7, so they carry their creation time
+            created_at: user_dto.id().get_timestamp().map(|timestamp| timestamp.to_unix().0),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserPageResponse {
+    users: Vec<UserResponse>,
+    next_cursor: Option<Uuid>,
+}
+
+impl From<&Page<UserDto>> for UserPageResponse {
+    fn from(page: &Page<UserDto>) -> Self {
+        Self {
+            users: page.items().iter().map(UserResponse::from).collect(),
+            next_cursor: page.next_cursor().copied(),
+        }
+    }
+}
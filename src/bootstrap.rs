@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::api::app_builder::AppBuilder;
+use crate::api::auth::signing_key::KeyStore;
+use crate::business::auth::login_throttle::LoginThrottle;
+use crate::business::auth::notifier::Notifier;
+use crate::business::auth::service::AuthService;
+use crate::business::user::breached_passwords::BreachedPasswords;
+use crate::business::user::password_validator::PasswordValidator;
+use crate::business::user::repository::UserRepositoryFactory;
+use crate::business::user::service::UserService;
+use crate::core::password_policy::PasswordPolicy;
+use crate::driver::breached_passwords::range_directory::RangeDirectory;
+use crate::driver::crypto::secret_cipher::SecretCipher;
+use crate::driver::dao::token::TokenDao;
+use crate::driver::dao::user::UserDao;
+use crate::driver::database::config_factory::ConfigFactory;
+use crate::driver::database::pool_adapter::PoolAdapter;
+use crate::driver::database::pool_factory::PoolFactory;
+use crate::driver::notifier::log_notifier::LogNotifier;
+use crate::driver::password::blocking_hasher::BlockingHasher;
+use crate::driver::password::scheme::PasswordScheme;
+use crate::driver::user_repository::in_memory_user_repository::InMemoryUserRepository;
+use crate::driver::user_repository::postgres_user_repository::PostgresUserRepositoryFactory;
+use crate::settings::{Cli, NotifierBackend, PasswordAlgorithm, PasswordSettings, RepositoryBackend, Settings, SettingsError};
+
+/// Reads the settings and validates them as a whole, including whether the
+/// drivers accept them, so every problem is reported at once.
+pub fn load_settings(cli: &Cli, env: impl Fn(&str) -> Option<String>) -> Result<Settings, SettingsError> {
+    let settings = Settings::read(cli, env)?;
+    let mut messages = settings.validate().err().map_or_else(Vec::new, |err| err.messages().to_vec());
+    if let Err(err) = password_scheme(&settings.password) {
+        messages.push(format!("password: {err}"));
+    }
+    if settings.database.backend == RepositoryBackend::Postgres {
+        // reads the url and the certificate files, so mistakes show up before the first connection
+        let config_factory = ConfigFactory::new(settings.database.clone());
+        if let Err(err) = config_factory.create().and_then(|_| config_factory.create_tls()) {
+            messages.push(format!("database: {err}"));
+        }
+    }
+    if !messages.is_empty() {
+        return Err(SettingsError::new(messages));
+    }
+    Ok(settings)
+}
+
+pub fn password_scheme(settings: &PasswordSettings) -> Result<PasswordScheme, String> {
+    match settings.algorithm {
+        PasswordAlgorithm::Bcrypt => PasswordScheme::bcrypt(settings.bcrypt_cost),
+        PasswordAlgorithm::Argon2id => {
+            PasswordScheme::argon2id(settings.argon2_memory_kib, settings.argon2_iterations, settings.argon2_parallelism)
+        }
+    }
+}
+
+/// Wires the services of the standalone server. The settings are expected to
+/// be validated already, see `load_settings`.
+pub async fn from_settings(settings: Settings) -> AppBuilder {
+    let token_pepper = settings.tokens.pepper.as_ref().expect("tokens.pepper is validated");
+    crate::core::token::init_pepper(token_pepper.expose().as_bytes());
+
+    let key_store = Arc::new(
+        KeyStore::load(&settings.jwt.key_directory, settings.jwt.key_algorithm, settings.jwt.ttl())
+            .expect("could not load signing keys"),
+    );
+    if let Some(interval) = settings.jwt.key_rotation_interval_secs {
+        key_store.clone().spawn_rotation(Duration::from_secs(interval));
+    }
+
+    let user_repositories: Arc<dyn UserRepositoryFactory> = match settings.database.backend {
+        RepositoryBackend::Postgres => {
+            let mfa_encryption_key = settings.mfa.encryption_key().expect("mfa.encryption_key is validated");
+            let cipher = Arc::new(SecretCipher::new(&mfa_encryption_key).expect("invalid mfa.encryption_key"));
+
+            let mut pool_factory = PoolFactory::new(ConfigFactory::new(settings.database.clone()));
+            let pool = pool_factory.create().await;
+            let pool_adapter = Arc::new(PoolAdapter::new(pool));
+            Arc::new(PostgresUserRepositoryFactory::new(pool_adapter, UserDao::new(cipher), TokenDao))
+        }
+        RepositoryBackend::Memory => {
+            warn!("the memory user repository keeps users in process memory, they are lost on restart");
+            Arc::new(InMemoryUserRepository::default())
+        }
+    };
+    let password_hasher = Arc::new(BlockingHasher::new(
+        password_scheme(&settings.password).expect("password settings are validated"),
+        settings.password.hashing_concurrency(),
+    ));
+    let breached_passwords = settings
+        .password
+        .breached_passwords_directory
+        .clone()
+        .map(|directory| Arc::new(RangeDirectory::new(directory)) as Arc<dyn BreachedPasswords>);
+    let password_validator = Arc::new(PasswordValidator::new(
+        PasswordPolicy::new(settings.password.min_length),
+        breached_passwords,
+    ));
+    let user_service = Arc::new(UserService::new(
+        user_repositories.clone(),
+        password_hasher.clone(),
+        password_validator.clone(),
+    ));
+    let notifier: Arc<dyn Notifier> = match settings.notifier.backend.expect("notifier.backend is validated") {
+        NotifierBackend::Log => {
+            warn!("the log notifier writes password reset tokens in plain text, use it for development only");
+            Arc::new(LogNotifier::new(settings.notifier.file.clone()))
+        }
+    };
+    let auth_service = Arc::new(AuthService::new(
+        user_repositories.clone(),
+        notifier,
+        password_hasher,
+        password_validator,
+        settings.lockout.account_policy(),
+        LoginThrottle::new(settings.lockout.client_policy()),
+        settings.tokens.lifetimes(),
+    ));
+
+    if let (Some(username), Some(password)) = (&settings.admin.username, &settings.admin.password) {
+        user_service
+            .bootstrap_admin(username, password.expose())
+            .await
+            .expect("could not bootstrap admin user");
+    }
+
+    let route_prefix = settings.server.route_prefix.clone();
+    AppBuilder::new(Arc::new(settings), user_service, auth_service, key_store).with_prefix(&route_prefix)
+}
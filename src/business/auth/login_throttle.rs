@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::warn;
+
+use crate::core::error::ThrottledError;
+use crate::core::lockout::LockoutPolicy;
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug)]
+struct ClientAttempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Counts failed logins per client IP in memory. A client's record is
+/// forgotten once it has not failed for the policy's maximum delay.
+#[derive(Debug)]
+pub struct LoginThrottle {
+    policy: LockoutPolicy,
+    clients: Mutex<HashMap<String, ClientAttempts>>,
+}
+
+impl LoginThrottle {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+    pub fn check(&self, ip_address: &str) -> Result<(), ThrottledError> {
+        let clients = self.clients.lock().unwrap();
+        let remaining = clients
+            .get(ip_address)
+            .and_then(|attempts| attempts.locked_until)
+            .and_then(|locked_until| locked_until.checked_duration_since(Instant::now()));
+        match remaining {
+            Some(remaining) if !remaining.is_zero() => Err(ThrottledError::TooManyAttempts { retry_after: remaining }),
+            _ => Ok(()),
+        }
+    }
+    pub fn record_failure(&self, ip_address: &str) {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED_CLIENTS {
+            clients.retain(|_, attempts| !self.is_stale(attempts, now));
+        }
+        let attempts = clients.entry(ip_address.to_owned()).or_insert(ClientAttempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if self.is_stale(attempts, now) {
+            attempts.failures = 0;
+        }
+        attempts.failures += 1;
+        attempts.last_failure = now;
+        if let Some(delay) = self.policy.delay(attempts.failures) {
+            warn!(
+                target: "security",
+                "throttling ip_address={:?} for {:?} after {} failed login attempts",
+                ip_address,
+                delay,
+                attempts.failures
+            );
+            attempts.locked_until = Some(now + delay);
+        }
+    }
+    fn is_stale(&self, attempts: &ClientAttempts, now: Instant) -> bool {
+        now.duration_since(attempts.last_failure) > self.policy.max_delay()
+    }
+}
@@ -1,2 +1,4 @@
+pub mod login_throttle;
+pub mod notifier;
 pub mod request;
 pub mod service;
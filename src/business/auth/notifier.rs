@@ -0,0 +1,9 @@
+use async_trait::async_trait;
+
+use crate::business::error::BusinessError;
+
+/// Delivers messages to users outside of the HTTP response, e.g. by email.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send_password_reset(&self, username: &str, reset_token: &str) -> Result<(), BusinessError>;
+}
@@ -14,3 +14,70 @@ impl LoginUserRequest {
         &self.password
     }
 }
+
+#[derive(Deserialize, Debug)]
+pub struct ChangePasswordRequest {
+    old_password: String,
+    new_password: String,
+}
+
+impl ChangePasswordRequest {
+    pub fn old_password(&self) -> &str {
+        &self.old_password
+    }
+    pub fn new_password(&self) -> &str {
+        &self.new_password
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ForgotPasswordRequest {
+    username: String,
+}
+
+impl ForgotPasswordRequest {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetPasswordRequest {
+    token: String,
+    new_password: String,
+}
+
+impl ResetPasswordRequest {
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+    pub fn new_password(&self) -> &str {
+        &self.new_password
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MfaLoginRequest {
+    challenge: String,
+    code: String,
+}
+
+impl MfaLoginRequest {
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmMfaRequest {
+    code: String,
+}
+
+impl ConfirmMfaRequest {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
@@ -1,44 +1,265 @@
 use std::sync::Arc;
 
 use log::debug;
+use uuid::Uuid;
 
-use crate::business::auth::request::LoginUserRequest;
+use crate::business::auth::login_throttle::LoginThrottle;
+use crate::business::auth::notifier::Notifier;
+use crate::business::auth::request::{
+    ChangePasswordRequest, ConfirmMfaRequest, ForgotPasswordRequest, LoginUserRequest,
+    MfaLoginRequest, ResetPasswordRequest,
+};
 use crate::business::error::BusinessError;
-use crate::business::user::repository::UserRepository;
+use crate::business::user::password_validator::PasswordValidator;
+use crate::business::user::repository::{StaleRetry, UserRepository, UserRepositoryFactory};
 use crate::core::error::AuthenticationError;
-use crate::core::user::UserDto;
+use crate::core::lockout::LockoutPolicy;
+use crate::core::password::PasswordHasher;
+use crate::core::token::{ClientInfo, TokenDto, TokenLifetimes};
+use crate::core::user::{LoginOutcome, User, UserDto};
+
+pub enum LoginResult {
+    Authenticated(UserDto),
+    MfaRequired(String),
+}
 
-#[derive(Debug)]
 pub struct AuthService {
-    user_repository: Arc<UserRepository>,
+    user_repositories: Arc<dyn UserRepositoryFactory>,
+    notifier: Arc<dyn Notifier>,
+    password_hasher: Arc<dyn PasswordHasher>,
+    password_validator: Arc<PasswordValidator>,
+    lockout_policy: LockoutPolicy,
+    login_throttle: LoginThrottle,
+    token_lifetimes: TokenLifetimes,
 }
 
 impl AuthService {
-    pub fn new(user_repository: Arc<UserRepository>) -> Self {
-        Self { user_repository }
-    }
-    pub async fn login(&self, request: LoginUserRequest) -> Result<UserDto, BusinessError> {
-        debug!("AuthService.login() with inputs: request={:?}", request);
-        let mut user = self.user_repository.find_by_username(request.username()).await?
-            .ok_or(AuthenticationError::new("invalid credentials"))?;
-        user.login(request.password())?;
-        self.user_repository.update(&user).await?;
+    pub fn new(
+        user_repositories: Arc<dyn UserRepositoryFactory>,
+        notifier: Arc<dyn Notifier>,
+        password_hasher: Arc<dyn PasswordHasher>,
+        password_validator: Arc<PasswordValidator>,
+        lockout_policy: LockoutPolicy,
+        login_throttle: LoginThrottle,
+        token_lifetimes: TokenLifetimes,
+    ) -> Self {
+        Self {
+            user_repositories,
+            notifier,
+            password_hasher,
+            password_validator,
+            lockout_policy,
+            login_throttle,
+            token_lifetimes,
+        }
+    }
+    pub async fn login(
+        &self,
+        request: LoginUserRequest,
+        client_info: ClientInfo,
+    ) -> Result<LoginResult, BusinessError> {
+        debug!("AuthService.login() with inputs: request={:?}, client_info={:?}", request, client_info);
+        let ip_address = client_info.ip_address().map(str::to_owned);
+        if let Some(ip_address) = &ip_address {
+            self.login_throttle.check(ip_address)?;
+        }
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        let (user, result) = loop {
+            let Some(mut user) = user_repository.find_by_username(request.username()).await? else {
+                self.record_failed_login(ip_address.as_deref());
+                return Err(AuthenticationError::new("invalid credentials").into());
+            };
+            user.check_lockout()?;
+            // persist even on failure, the failed attempt counts towards the lockout
+            let result = user
+                .login(
+                    request.password(),
+                    client_info.clone(),
+                    &self.lockout_policy,
+                    &self.token_lifetimes,
+                    self.password_hasher.as_ref(),
+                )
+                .await;
+            if retry.save(user_repository.as_ref(), &user).await? {
+                break (user, result);
+            }
+        };
+        let outcome = result.inspect_err(|_| self.record_failed_login(ip_address.as_deref()))?;
+        match outcome {
+            LoginOutcome::Authenticated => Ok(LoginResult::Authenticated(user.to_dto())),
+            LoginOutcome::MfaRequired(challenge) => Ok(LoginResult::MfaRequired(challenge)),
+        }
+    }
+    pub async fn complete_mfa_login(
+        &self,
+        request: MfaLoginRequest,
+        client_info: ClientInfo,
+    ) -> Result<UserDto, BusinessError> {
+        debug!("AuthService.complete_mfa_login() with inputs: client_info={:?}", client_info);
+        if let Some(ip_address) = client_info.ip_address() {
+            self.login_throttle.check(ip_address)?;
+        }
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        let (user, result) = loop {
+            let mut user = user_repository.find_by_token(request.challenge()).await?
+                .ok_or(AuthenticationError::new("invalid challenge"))?;
+            user.check_lockout()?;
+            // persist even on failure, the challenge is single-use
+            let result = user.complete_mfa_login(
+                request.challenge(),
+                request.code(),
+                &self.lockout_policy,
+                &self.token_lifetimes,
+            );
+            if retry.save(user_repository.as_ref(), &user).await? {
+                break (user, result);
+            }
+        };
+        result.inspect_err(|_| self.record_failed_login(client_info.ip_address()))?;
         Ok(user.to_dto())
     }
-    pub async fn refresh(&self, refresh_token: &str) -> Result<UserDto, BusinessError> {
-        debug!("AuthService.refresh() with inputs: refresh_token={:?}", refresh_token);
-        let mut user = self.user_repository.find_by_token(refresh_token).await?
-            .ok_or(AuthenticationError::new("invalid token"))?;
-        user.refresh(refresh_token)?;
-        self.user_repository.update(&user).await?;
+    pub async fn enroll_mfa(&self, username: &str) -> Result<(String, String), BusinessError> {
+        debug!("AuthService.enroll_mfa() with inputs: username={:?}", username);
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = Self::find_user(user_repository.as_ref(), username).await?;
+        let enrollment = user.enroll_mfa()?;
+        user_repository.update(&user).await?;
+        Ok(enrollment)
+    }
+    pub async fn confirm_mfa(
+        &self,
+        username: &str,
+        request: ConfirmMfaRequest,
+    ) -> Result<Vec<String>, BusinessError> {
+        debug!("AuthService.confirm_mfa() with inputs: username={:?}", username);
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = Self::find_user(user_repository.as_ref(), username).await?;
+        let recovery_codes = user.confirm_mfa(request.code())?;
+        user_repository.update(&user).await?;
+        Ok(recovery_codes)
+    }
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+        client_info: ClientInfo,
+    ) -> Result<UserDto, BusinessError> {
+        debug!(
+            "AuthService.refresh() with inputs: refresh_token={:?}, client_info={:?}",
+            refresh_token, client_info
+        );
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        let (user, result) = loop {
+            let mut user = user_repository.find_by_token(refresh_token).await?
+                .ok_or(AuthenticationError::new("invalid token"))?;
+            // persist even on failure, a detected token reuse revokes the whole family
+            let result = user.refresh(refresh_token, client_info.clone(), &self.token_lifetimes);
+            if retry.save(user_repository.as_ref(), &user).await? {
+                break (user, result);
+            }
+        };
+        result?;
         Ok(user.to_dto())
     }
     pub async fn logout(&self, refresh_token: &str) -> Result<(), BusinessError> {
         debug!("AuthService.logout() with inputs: refresh_token={:?}", refresh_token);
-        let mut user = self.user_repository.find_by_token(refresh_token).await?
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        loop {
+            let mut user = user_repository.find_by_token(refresh_token).await?
+                .ok_or(AuthenticationError::new("invalid token"))?;
+            user.logout(refresh_token)?;
+            if retry.save(user_repository.as_ref(), &user).await? {
+                return Ok(());
+            }
+        }
+    }
+    pub async fn sessions(&self, username: &str) -> Result<Vec<TokenDto>, BusinessError> {
+        debug!("AuthService.sessions() with inputs: username={:?}", username);
+        let user_repository = self.user_repositories.open().await?;
+        let user = Self::find_user(user_repository.as_ref(), username).await?;
+        Ok(user.sessions())
+    }
+    pub async fn revoke_session(&self, username: &str, session_id: &Uuid) -> Result<(), BusinessError> {
+        debug!("AuthService.revoke_session() with inputs: username={:?}, session_id={:?}", username, session_id);
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = Self::find_user(user_repository.as_ref(), username).await?;
+        if !user.revoke_session(session_id) {
+            return Err(BusinessError::NotFound("session not found".to_owned()));
+        }
+        user_repository.update(&user).await?;
+        Ok(())
+    }
+    pub async fn logout_all(&self, username: &str) -> Result<(), BusinessError> {
+        debug!("AuthService.logout_all() with inputs: username={:?}", username);
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = Self::find_user(user_repository.as_ref(), username).await?;
+        user.logout_all();
+        user_repository.update(&user).await?;
+        Ok(())
+    }
+    pub async fn change_password(
+        &self,
+        username: &str,
+        request: ChangePasswordRequest,
+        current_refresh_token: Option<&str>,
+    ) -> Result<(), BusinessError> {
+        debug!("AuthService.change_password() with inputs: username={:?}", username);
+        self.password_validator
+            .validate(username, request.new_password())
+            .await?;
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = Self::find_user(user_repository.as_ref(), username).await?;
+        user.change_password(
+            request.old_password(),
+            request.new_password(),
+            current_refresh_token,
+            self.password_hasher.as_ref(),
+        )
+        .await?;
+        user_repository.update(&user).await?;
+        Ok(())
+    }
+    /// Succeeds for unknown usernames as well, so the endpoint cannot be used
+    /// to probe which accounts exist.
+    pub async fn forgot_password(&self, request: ForgotPasswordRequest) -> Result<(), BusinessError> {
+        debug!("AuthService.forgot_password() with inputs: request={:?}", request);
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        while let Some(mut user) = user_repository.find_by_username(request.username()).await? {
+            let reset_token = user.request_password_reset(&self.token_lifetimes);
+            if retry.save(user_repository.as_ref(), &user).await? {
+                self.notifier
+                    .send_password_reset(request.username(), &reset_token)
+                    .await?;
+                break;
+            }
+        }
+        Ok(())
+    }
+    pub async fn reset_password(&self, request: ResetPasswordRequest) -> Result<(), BusinessError> {
+        debug!("AuthService.reset_password()");
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = user_repository.find_by_token(request.token()).await?
             .ok_or(AuthenticationError::new("invalid token"))?;
-        user.logout(refresh_token)?;
-        self.user_repository.update(&user).await?;
+        self.password_validator
+            .validate(user.username(), request.new_password())
+            .await?;
+        user.reset_password(request.token(), request.new_password(), self.password_hasher.as_ref())
+            .await?;
+        user_repository.update(&user).await?;
         Ok(())
     }
+    fn record_failed_login(&self, ip_address: Option<&str>) {
+        if let Some(ip_address) = ip_address {
+            self.login_throttle.record_failure(ip_address);
+        }
+    }
+    async fn find_user(user_repository: &dyn UserRepository, username: &str) -> Result<User, BusinessError> {
+        let user = user_repository.find_by_username(username).await?
+            .ok_or(AuthenticationError::new("unknown user"))?;
+        Ok(user)
+    }
 }
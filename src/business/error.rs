@@ -1,31 +1,57 @@
-use serde::Serialize;
+use std::fmt::{Display, Formatter};
 
-use crate::core::error::AuthenticationError;
-use crate::driver::error::DriverError;
+use crate::core::error::{AuthenticationError, ThrottledError, ValidationError};
+use crate::business::user::repository::RepositoryError;
 
-#[derive(Debug, Serialize)]
-pub struct BusinessError {
-    message: String,
+#[derive(Debug)]
+pub enum BusinessError {
+    NotFound(String),
+    Conflict(String),
+    Unauthorized(String),
+    Validation(ValidationError),
+    Throttled(ThrottledError),
+    /// A failure the client cannot do anything about, its details must not
+    /// leave the server.
+    Internal(String),
 }
 
-impl std::fmt::Display for BusinessError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error: {}", self.message)
+impl Display for BusinessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusinessError::NotFound(message)
+            | BusinessError::Conflict(message)
+            | BusinessError::Unauthorized(message) => write!(f, "{message}"),
+            BusinessError::Validation(error) => write!(f, "{error}"),
+            BusinessError::Throttled(error) => write!(f, "{error}"),
+            BusinessError::Internal(message) => write!(f, "{message}"),
+        }
     }
 }
 
-impl From<DriverError> for BusinessError {
-    fn from(value: DriverError) -> Self {
-        Self {
-            message: value.message().to_string(),
+impl From<RepositoryError> for BusinessError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::UsernameTaken => BusinessError::Conflict("username is already taken".to_owned()),
+            RepositoryError::Stale => BusinessError::Conflict("user was modified concurrently, try again".to_owned()),
+            error => BusinessError::Internal(error.to_string()),
         }
     }
 }
 
 impl From<AuthenticationError> for BusinessError {
     fn from(value: AuthenticationError) -> Self {
-        Self {
-            message: value.message().to_owned(),
-        }
+        BusinessError::Unauthorized(value.message().to_owned())
+    }
+}
+
+impl From<ThrottledError> for BusinessError {
+    fn from(value: ThrottledError) -> Self {
+        BusinessError::Throttled(value)
+    }
+}
+
+impl From<ValidationError> for BusinessError {
+    fn from(value: ValidationError) -> Self {
+        BusinessError::Validation(value)
     }
 }
@@ -0,0 +1,9 @@
+use async_trait::async_trait;
+
+use crate::business::error::BusinessError;
+
+/// A corpus of passwords known from data breaches.
+#[async_trait]
+pub trait BreachedPasswords: Send + Sync {
+    async fn contains(&self, password: &str) -> Result<bool, BusinessError>;
+}
@@ -1,3 +1,5 @@
+pub mod breached_passwords;
+pub mod password_validator;
 pub mod repository;
 pub mod request;
 pub mod service;
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::business::error::BusinessError;
+use crate::business::user::breached_passwords::BreachedPasswords;
+use crate::core::error::{FieldError, ValidationError};
+use crate::core::password_policy::PasswordPolicy;
+
+pub struct PasswordValidator {
+    policy: PasswordPolicy,
+    breached_passwords: Option<Arc<dyn BreachedPasswords>>,
+}
+
+impl PasswordValidator {
+    pub fn new(policy: PasswordPolicy, breached_passwords: Option<Arc<dyn BreachedPasswords>>) -> Self {
+        Self {
+            policy,
+            breached_passwords,
+        }
+    }
+    pub async fn validate(&self, username: &str, password: &str) -> Result<(), BusinessError> {
+        debug!("PasswordValidator.validate() with inputs: username={:?}", username);
+        let mut errors = self.policy.check(username, password);
+        if let Some(breached_passwords) = &self.breached_passwords {
+            if breached_passwords.contains(password).await? {
+                errors.push(FieldError::new(
+                    "password",
+                    "breached",
+                    "appears in a list of breached passwords".to_owned(),
+                ));
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(ValidationError::new(errors).into())
+    }
+}
@@ -1,78 +1,92 @@
+use std::fmt::{Display, Formatter};
+
+use async_trait::async_trait;
 use log::debug;
 use uuid::Uuid;
 
+use crate::core::page::{Page, SortOrder};
 use crate::core::user::User;
-use crate::driver::dao::token::TokenDao;
-use crate::driver::dao::user::UserDao;
-use crate::driver::error::DriverError;
 
+const MAX_STALE_SAVES: u32 = 10;
+
+/// Failure of a `UserRepository`, classified so services can react to it
+/// without knowing the storage behind it.
 #[derive(Debug)]
-pub struct UserRepository {
-    user_dao: UserDao,
-    token_dao: TokenDao,
+pub enum RepositoryError {
+    /// Another user already has the username.
+    UsernameTaken,
+    /// Another user already has the id.
+    DuplicateId,
+    /// Concurrent writes kept aborting each other, trying again later may work.
+    Retryable(String),
+    /// The user was changed by someone else since it was loaded.
+    Stale,
+    Other(String),
 }
 
-impl UserRepository {
-    pub fn new(user_dao: UserDao, token_dao: TokenDao) -> Self {
-        Self {
-            user_dao,
-            token_dao,
-        }
-    }
-    pub async fn create(&self, user: &User) -> Result<(), DriverError> {
-        debug!("UserRepository.create() with inputs: user={:?}", user);
-        let user_dto = user.to_dto();
-        for token_dto in user_dto.tokens() {
-            self.token_dao.create(token_dto).await?;
-        }
-        self.user_dao.create(&user_dto).await
-    }
-    pub async fn update(&self, user: &User) -> Result<(), DriverError> {
-        debug!("UserRepository.update() with inputs: user={:?}", user);
-        let user_dto = user.to_dto();
-        for token_dto in user_dto.tokens() {
-            self.token_dao.save(token_dto).await?;
-        }
-        self.user_dao.update(&user_dto).await
-    }
-    pub async fn delete_by_id(&self, user_id: &Uuid) -> Result<(), DriverError> {
-        debug!("UserRepository.delete_by_id() with inputs: user_id={:?}", user_id);
-        self.user_dao.delete_by_id(user_id).await?;
-        self.token_dao.delete_by_user_id(user_id).await?;
-        Ok(())
-    }
-    pub async fn find_by_id(&self, user_id: &Uuid) -> Result<Option<User>, DriverError> {
-        debug!("UserRepository.find_by_id() with inputs: user_id={:?}", user_id);
-        let user_dto = self.user_dao.find_by_id(user_id).await?;
-        let vec_of_token_dtos = self.token_dao.find_by_user_id(user_id).await?;
-        Ok(user_dto.map(move |user_dto| User::from_dto(&user_dto, &vec_of_token_dtos)))
-    }
-    pub async fn find_by_username(&self, username: &str) -> Result<Option<User>, DriverError> {
-        debug!("UserRepository.find_by_username() with inputs: username={:?}", username);
-        if let Some(user_dto) = self.user_dao.find_by_username(username).await? {
-            let vec_of_token_dtos = self.token_dao.find_by_user_id(user_dto.id()).await?;
-            return Ok(Some(User::from_dto(&user_dto, &vec_of_token_dtos)));
+impl Display for RepositoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryError::UsernameTaken => write!(f, "username is already taken"),
+            RepositoryError::DuplicateId => write!(f, "user id is already taken"),
+            RepositoryError::Retryable(message) => write!(f, "retryable repository error: {message}"),
+            RepositoryError::Stale => write!(f, "user was modified since it was loaded"),
+            RepositoryError::Other(message) => write!(f, "repository error: {message}"),
         }
-        Ok(None)
     }
-    pub async fn find_by_token(&self, key: &str) -> Result<Option<User>, DriverError> {
-        debug!("UserRepository.find_by_username() with inputs: key={:?}", key);
-        if let Some(user_dto) = self.user_dao.find_by_token(key).await? {
-            let vec_of_token_dtos = self.token_dao.find_by_user_id(user_dto.id()).await?;
-            return Ok(Some(User::from_dto(&user_dto, &vec_of_token_dtos)));
-        }
-        Ok(None)
-    }
-    pub async fn find_all(&self) -> Result<Vec<User>, DriverError> {
-        debug!("UserRepository.find_all()");
-        let user_dtos = self.user_dao.find_all().await?;
-        let mut vec_of_user = vec!();
-        for user_dto in user_dtos {
-            let vec_of_token_dtos = self.token_dao.find_by_user_id(user_dto.id()).await?;
-            vec_of_user.push(User::from_dto(&user_dto, &vec_of_token_dtos))
+}
+
+/// Opens the `UserRepository` a single request works with. Services open one
+/// per operation and run every load and save of that operation on it, so a
+/// database backed repository keeps one connection for the whole request.
+#[async_trait]
+pub trait UserRepositoryFactory: Send + Sync {
+    async fn open(&self) -> Result<Box<dyn UserRepository>, RepositoryError>;
+}
+
+/// Persists `User` aggregates together with their tokens and MFA credentials.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Fails with `RepositoryError::UsernameTaken` when the username is taken.
+    async fn create(&self, user: &User) -> Result<(), RepositoryError>;
+    /// Fails with `RepositoryError::Stale` when the user was updated or
+    /// deleted since it was loaded, nothing is written then.
+    async fn update(&self, user: &User) -> Result<(), RepositoryError>;
+    async fn delete_by_id(&self, user_id: &Uuid) -> Result<(), RepositoryError>;
+    async fn find_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError>;
+    /// Looks the user up by the plain key of one of its tokens.
+    async fn find_by_token(&self, key: &str) -> Result<Option<User>, RepositoryError>;
+    /// Returns at most `limit` users after `cursor` in id order.
+    async fn find_page(
+        &self,
+        username_prefix: Option<&str>,
+        cursor: Option<&Uuid>,
+        order: SortOrder,
+        limit: u32,
+    ) -> Result<Page<User>, RepositoryError>;
+}
+
+/// Saves a change the system makes on its own behalf, like a counted failed
+/// login or an issued token. Such a change must not fail only because a
+/// concurrent request saved the same user first: while `save` returns
+/// `Ok(false)` the caller loads the user again and repeats the change. Edits a
+/// client asked for are saved with `UserRepository::update` and fail with
+/// `RepositoryError::Stale` instead.
+#[derive(Debug, Default)]
+pub struct StaleRetry {
+    attempt: u32,
+}
+
+impl StaleRetry {
+    pub async fn save(&mut self, user_repository: &dyn UserRepository, user: &User) -> Result<bool, RepositoryError> {
+        match user_repository.update(user).await {
+            Err(RepositoryError::Stale) if self.attempt + 1 < MAX_STALE_SAVES => {
+                self.attempt += 1;
+                debug!("StaleRetry.save() reloading the user after attempt={:?}", self.attempt);
+                Ok(false)
+            }
+            result => result.map(|()| true),
         }
-        let result = Ok(vec_of_user);
-        debug!("UserRepository.find_all() with output: {:?}", result);
-        result
     }
 }
@@ -1,6 +1,12 @@
 use serde::Deserialize;
 use uuid::Uuid;
 
+use crate::core::page::SortOrder;
+use crate::core::role::Role;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
 #[derive(Deserialize, Debug)]
 pub struct RegisterUserRequest {
     username: String,
@@ -16,6 +22,31 @@ impl RegisterUserRequest {
     }
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ListUsersRequest {
+    limit: Option<u32>,
+    cursor: Option<Uuid>,
+    username_prefix: Option<String>,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+impl ListUsersRequest {
+    /// Clamped to `1..=MAX_PAGE_SIZE` so a client cannot ask for the whole table.
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+    pub fn cursor(&self) -> Option<&Uuid> {
+        self.cursor.as_ref()
+    }
+    pub fn username_prefix(&self) -> Option<&str> {
+        self.username_prefix.as_deref().filter(|prefix| !prefix.is_empty())
+    }
+    pub fn order(&self) -> SortOrder {
+        self.order
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct DeleteUserRequest {
     user_id: Uuid,
@@ -26,3 +57,18 @@ impl DeleteUserRequest {
         &self.user_id
     }
 }
+
+#[derive(Deserialize, Debug)]
+pub struct GrantRoleRequest {
+    user_id: Uuid,
+    role: Role,
+}
+
+impl GrantRoleRequest {
+    pub fn user_id(&self) -> &Uuid {
+        &self.user_id
+    }
+    pub fn role(&self) -> Role {
+        self.role
+    }
+}
@@ -4,36 +4,102 @@ use log::debug;
 use uuid::Uuid;
 
 use crate::business::error::BusinessError;
-use crate::business::user::repository::UserRepository;
-use crate::business::user::request::{RegisterUserRequest, DeleteUserRequest};
+use crate::business::user::password_validator::PasswordValidator;
+use crate::business::user::repository::{StaleRetry, UserRepositoryFactory};
+use crate::business::user::request::{
+    DeleteUserRequest, GrantRoleRequest, ListUsersRequest, RegisterUserRequest,
+};
+use crate::core::page::Page;
+use crate::core::password::PasswordHasher;
+use crate::core::role::Role;
 use crate::core::user::{User, UserDto};
 
 pub struct UserService {
-    user_repository: Arc<UserRepository>,
+    user_repositories: Arc<dyn UserRepositoryFactory>,
+    password_hasher: Arc<dyn PasswordHasher>,
+    password_validator: Arc<PasswordValidator>,
 }
 
 impl UserService {
-    pub fn new(user_repository: Arc<UserRepository>) -> Self {
-        Self { user_repository }
+    pub fn new(
+        user_repositories: Arc<dyn UserRepositoryFactory>,
+        password_hasher: Arc<dyn PasswordHasher>,
+        password_validator: Arc<PasswordValidator>,
+    ) -> Self {
+        Self {
+            user_repositories,
+            password_hasher,
+            password_validator,
+        }
     }
-    pub async fn index(&self) -> Result<Vec<UserDto>, BusinessError> {
-        debug!("UserService.index()");
-        let vec_of_user = self.user_repository.find_all().await?;
-        Ok(vec_of_user.iter().map(|user| user.to_dto()).collect())
+    pub async fn index(&self, request: &ListUsersRequest) -> Result<Page<UserDto>, BusinessError> {
+        debug!("UserService.index() with inputs: request={:?}", request);
+        let user_repository = self.user_repositories.open().await?;
+        let page_of_user = user_repository
+            .find_page(request.username_prefix(), request.cursor(), request.order(), request.limit())
+            .await?;
+        Ok(page_of_user.map(|user| user.to_dto()))
     }
-    pub async fn show(&self, id: Uuid) -> Result<Option<UserDto>, BusinessError> {
+    pub async fn show(&self, id: Uuid) -> Result<UserDto, BusinessError> {
         debug!("UserService.show() with inputs: id={:?}", id);
-        let user = self.user_repository.find_by_id(&id).await?;
-        Ok(user.map(|user| user.to_dto()))
+        let user_repository = self.user_repositories.open().await?;
+        let user = user_repository.find_by_id(&id).await?
+            .ok_or(BusinessError::NotFound("user not found".to_owned()))?;
+        Ok(user.to_dto())
     }
     pub async fn register(&self, request: RegisterUserRequest) -> Result<UserDto, BusinessError> {
         debug!("UserService.register() with inputs: request={:?}", request);
-        let new_user = User::new(request.username().to_owned(), request.password().to_owned());
-        self.user_repository.create(&new_user).await?;
+        self.password_validator
+            .validate(request.username(), request.password())
+            .await?;
+        let user_repository = self.user_repositories.open().await?;
+        if user_repository.find_by_username(request.username()).await?.is_some() {
+            return Err(BusinessError::Conflict("username is already taken".to_owned()));
+        }
+        let new_user = User::new(
+            request.username().to_owned(),
+            request.password().to_owned(),
+            self.password_hasher.as_ref(),
+        )
+        .await;
+        // a concurrent registration can still take the name between the check and the insert
+        user_repository.create(&new_user).await?;
         Ok(new_user.to_dto())
     }
     pub async fn delete(&self, request: DeleteUserRequest) -> Result<(), BusinessError> {
-        self.user_repository.delete_by_id(request.user_id()).await
+        let user_repository = self.user_repositories.open().await?;
+        user_repository.delete_by_id(request.user_id()).await
             .map_err(BusinessError::from)
     }
+    pub async fn grant_role(&self, request: GrantRoleRequest) -> Result<UserDto, BusinessError> {
+        debug!("UserService.grant_role() with inputs: request={:?}", request);
+        let user_repository = self.user_repositories.open().await?;
+        let mut user = user_repository.find_by_id(request.user_id()).await?
+            .ok_or(BusinessError::NotFound("user not found".to_owned()))?;
+        user.grant(request.role());
+        user_repository.update(&user).await?;
+        Ok(user.to_dto())
+    }
+    pub async fn bootstrap_admin(&self, username: &str, password: &str) -> Result<(), BusinessError> {
+        debug!("UserService.bootstrap_admin() with inputs: username={:?}", username);
+        let user_repository = self.user_repositories.open().await?;
+        let mut retry = StaleRetry::default();
+        loop {
+            match user_repository.find_by_username(username).await? {
+                Some(mut user) => {
+                    user.grant(Role::Admin);
+                    if retry.save(user_repository.as_ref(), &user).await? {
+                        return Ok(());
+                    }
+                }
+                None => {
+                    let mut user =
+                        User::new(username.to_owned(), password.to_owned(), self.password_hasher.as_ref()).await;
+                    user.grant(Role::Admin);
+                    user_repository.create(&user).await?;
+                    return Ok(());
+                }
+            }
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
 
-use actix_web::http::StatusCode;
-use actix_web::ResponseError;
+use serde::Serialize;
 
 #[derive(Debug)]
 pub struct AuthenticationError {
@@ -25,8 +25,66 @@ impl Display for AuthenticationError {
     }
 }
 
-impl ResponseError for AuthenticationError {
-    fn status_code(&self) -> StatusCode {
-        StatusCode::UNAUTHORIZED
+/// Login attempts are blocked for a while, either because the account is
+/// locked or because the client failed too often.
+#[derive(Debug, Clone)]
+pub enum ThrottledError {
+    AccountLocked { retry_after: Duration },
+    TooManyAttempts { retry_after: Duration },
+}
+
+impl ThrottledError {
+    pub fn retry_after(&self) -> Duration {
+        match self {
+            ThrottledError::AccountLocked { retry_after } => *retry_after,
+            ThrottledError::TooManyAttempts { retry_after } => *retry_after,
+        }
+    }
+}
+
+impl Display for ThrottledError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottledError::AccountLocked { .. } => write!(f, "account is temporarily locked"),
+            ThrottledError::TooManyAttempts { .. } => write!(f, "too many failed login attempts"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    field: &'static str,
+    code: &'static str,
+    message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, code: &'static str, message: String) -> Self {
+        Self {
+            field,
+            code,
+            message,
+        }
+    }
+}
+
+/// One or more fields of a request were rejected.
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationError {
+    pub fn new(errors: Vec<FieldError>) -> Self {
+        Self { errors }
+    }
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "validation failed")
     }
 }
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+/// Exponential backoff applied once a number of consecutive failed login
+/// attempts has been reached.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+        }
+    }
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+    /// How long to lock after the given number of consecutive failures; the
+    /// delay doubles with every failure past the threshold.
+    pub fn delay(&self, failed_attempts: u32) -> Option<Duration> {
+        let excess = failed_attempts.checked_sub(self.max_attempts)?;
+        let factor = 2u32.checked_pow(excess).unwrap_or(u32::MAX);
+        Some(self.base_delay.saturating_mul(factor).min(self.max_delay))
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::{Debug, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32_NOPAD;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::core::error::AuthenticationError;
+use crate::core::token::Token;
+
+const TOTP_ISSUER: &str = "abcd";
+const TOTP_SECRET_BYTES: usize = 20;
+const TOTP_STEP_IN_SECS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const TOTP_SKEW_IN_STEPS: i64 = 1; // accept one step of clock drift in both directions
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_BYTES: usize = 5;
+
+/// RFC 6238 time-based one-time password credential of a user.
+pub struct Mfa {
+    secret: Vec<u8>,
+    is_confirmed: bool,
+    recovery_code_hashes: Vec<String>,
+    last_used_step: i64,
+}
+
+impl Mfa {
+    pub fn generate() -> Self {
+        Self {
+            secret: random_bytes(TOTP_SECRET_BYTES),
+            is_confirmed: false,
+            recovery_code_hashes: Vec::new(),
+            last_used_step: 0,
+        }
+    }
+    pub fn from_dto(mfa_dto: &MfaDto) -> Self {
+        Self {
+            secret: mfa_dto.secret().to_vec(),
+            is_confirmed: *mfa_dto.is_confirmed(),
+            recovery_code_hashes: mfa_dto.recovery_code_hashes().to_vec(),
+            last_used_step: *mfa_dto.last_used_step(),
+        }
+    }
+    pub fn to_dto(&self) -> MfaDto {
+        MfaDto::new(
+            self.secret.to_owned(),
+            self.is_confirmed,
+            self.recovery_code_hashes.to_owned(),
+            self.last_used_step,
+        )
+    }
+    pub fn is_confirmed(&self) -> bool {
+        self.is_confirmed
+    }
+    pub fn secret_base32(&self) -> String {
+        BASE32_NOPAD.encode(&self.secret)
+    }
+    pub fn otpauth_uri(&self, username: &str) -> String {
+        format!(
+            "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP_IN_SECS}",
+            issuer = urlencoding::encode(TOTP_ISSUER),
+            username = urlencoding::encode(username),
+            secret = self.secret_base32(),
+        )
+    }
+    /// Activates the credential with a first valid code and returns freshly
+    /// generated recovery codes; only their hashes are kept.
+    pub fn confirm(&mut self, code: &str) -> Result<Vec<String>, AuthenticationError> {
+        if self.is_confirmed {
+            return Err(AuthenticationError::new("two-factor authentication is already enabled"));
+        }
+        if !self.verify_totp(code) {
+            return Err(AuthenticationError::new("invalid code"));
+        }
+        let recovery_codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| BASE32_NOPAD.encode(&random_bytes(RECOVERY_CODE_BYTES)))
+            .collect::<Vec<_>>();
+        self.recovery_code_hashes = recovery_codes.iter().map(|code| Token::hash_key(code)).collect();
+        self.is_confirmed = true;
+        Ok(recovery_codes)
+    }
+    /// Accepts a current TOTP code or consumes one of the recovery codes.
+    pub fn verify(&mut self, code: &str) -> bool {
+        if self.verify_totp(code) {
+            return true;
+        }
+        let code = code.trim().to_uppercase();
+        if let Some(index) = self.recovery_code_hashes.iter().position(|hash| Token::key_matches_hash(&code, hash)) {
+            self.recovery_code_hashes.remove(index);
+            return true;
+        }
+        false
+    }
+    /// Each step can only be used once, so an observed code cannot be replayed.
+    fn verify_totp(&mut self, code: &str) -> bool {
+        let current_step = (SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System clock may have gone backwards")
+            .as_secs()
+            / TOTP_STEP_IN_SECS) as i64;
+        let matching_step = (current_step - TOTP_SKEW_IN_STEPS..=current_step + TOTP_SKEW_IN_STEPS)
+            .filter(|step| *step > self.last_used_step)
+            // compared as hashes, so the time taken does not tell how many digits were right
+            .find(|step| Token::key_matches_hash(code.trim(), &Token::hash_key(&self.hotp(*step as u64))));
+        if let Some(step) = matching_step {
+            self.last_used_step = step;
+            return true;
+        }
+        false
+    }
+    /// RFC 4226 HMAC-based one-time password with dynamic truncation.
+    fn hotp(&self, counter: u64) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &self.secret);
+        let tag = hmac::sign(&key, &counter.to_be_bytes());
+        let digest = tag.as_ref();
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+    }
+}
+
+impl Debug for Mfa {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mfa")
+            .field("is_confirmed", &self.is_confirmed)
+            .field("last_used_step", &self.last_used_step)
+            .finish_non_exhaustive()
+    }
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes
+}
+
+#[derive(Clone)]
+pub struct MfaDto {
+    secret: Vec<u8>,
+    is_confirmed: bool,
+    recovery_code_hashes: Vec<String>,
+    last_used_step: i64,
+}
+
+impl MfaDto {
+    pub fn new(
+        secret: Vec<u8>,
+        is_confirmed: bool,
+        recovery_code_hashes: Vec<String>,
+        last_used_step: i64,
+    ) -> Self {
+        Self {
+            secret,
+            is_confirmed,
+            recovery_code_hashes,
+            last_used_step,
+        }
+    }
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+    pub fn is_confirmed(&self) -> &bool {
+        &self.is_confirmed
+    }
+    pub fn recovery_code_hashes(&self) -> &[String] {
+        &self.recovery_code_hashes
+    }
+    pub fn last_used_step(&self) -> &i64 {
+        &self.last_used_step
+    }
+}
+
+impl Debug for MfaDto {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MfaDto")
+            .field("is_confirmed", &self.is_confirmed)
+            .field("last_used_step", &self.last_used_step)
+            .finish_non_exhaustive()
+    }
+}
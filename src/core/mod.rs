@@ -1,3 +1,9 @@
 pub mod error;
+pub mod lockout;
+pub mod mfa;
+pub mod page;
+pub mod password;
+pub mod password_policy;
+pub mod role;
 pub mod token;
 pub mod user;
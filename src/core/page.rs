@@ -0,0 +1,45 @@
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// One slice of a listing paginated by keyset on a UUIDv7 id. The cursor is
+/// the id of the last item, absent when there is nothing left.
+#[derive(Debug)]
+pub struct Page<T> {
+    items: Vec<T>,
+    next_cursor: Option<Uuid>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<Uuid>) -> Self {
+        Self { items, next_cursor }
+    }
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+    pub fn next_cursor(&self) -> Option<&Uuid> {
+        self.next_cursor.as_ref()
+    }
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
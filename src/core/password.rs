@@ -0,0 +1,13 @@
+use async_trait::async_trait;
+
+/// Hashes and verifies user passwords. Implementations are expected to keep
+/// the expensive work off the async executor.
+#[async_trait]
+pub trait PasswordHasher: Send + Sync {
+    async fn hash(&self, password: &str) -> String;
+    /// Returns `false` for a wrong password as well as for a malformed hash.
+    async fn verify(&self, password: &str, hash: &str) -> bool;
+    /// Whether the hash uses another algorithm or other parameters than new
+    /// hashes would.
+    fn needs_rehash(&self, hash: &str) -> bool;
+}
@@ -0,0 +1,33 @@
+use crate::core::error::FieldError;
+
+/// Rules a new password has to satisfy that need nothing but the credentials
+/// themselves.
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordPolicy {
+    min_length: usize,
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize) -> Self {
+        Self { min_length }
+    }
+    pub fn check(&self, username: &str, password: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if password.chars().count() < self.min_length {
+            errors.push(FieldError::new(
+                "password",
+                "too_short",
+                format!("must be at least {} characters long", self.min_length),
+            ));
+        }
+        let username = username.trim().to_lowercase();
+        if !username.is_empty() && password.to_lowercase().contains(&username) {
+            errors.push(FieldError::new(
+                "password",
+                "contains_username",
+                "must not contain the username".to_owned(),
+            ));
+        }
+        errors
+    }
+}
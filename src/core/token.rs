@@ -1,55 +1,229 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
 use std::time::{Duration, SystemTime};
 
-use rand::{thread_rng, Rng};
-use serde::Serialize;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
 use tokio_postgres::Row;
 use uuid::Uuid;
 
 use crate::core::error::AuthenticationError;
 
-const TOKEN_TTL: Duration = Duration::from_secs(60 * 60); // h = m * s
+const TOKEN_KEY_BYTES: usize = 32;
+
+static PEPPER: OnceLock<hmac::Key> = OnceLock::new();
+
+/// Sets the server-side secret that token keys are hashed with. Must be called
+/// once at startup before any token is created or looked up.
+pub fn init_pepper(pepper: &[u8]) {
+    if PEPPER.set(hmac::Key::new(hmac::HMAC_SHA256, pepper)).is_err() {
+        panic!("token pepper is already initialised");
+    }
+}
+
+fn pepper() -> &'static hmac::Key {
+    PEPPER.get().expect("token pepper is initialised at startup")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenPurpose {
+    Refresh,
+    PasswordReset,
+    MfaChallenge,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::Refresh => "refresh",
+            TokenPurpose::PasswordReset => "password_reset",
+            TokenPurpose::MfaChallenge => "mfa_challenge",
+        }
+    }
+}
+
+impl FromStr for TokenPurpose {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "refresh" => Ok(TokenPurpose::Refresh),
+            "password_reset" => Ok(TokenPurpose::PasswordReset),
+            "mfa_challenge" => Ok(TokenPurpose::MfaChallenge),
+            _ => Err(format!("unknown token purpose: {value}")),
+        }
+    }
+}
+
+/// How long a token of each purpose stays valid after it was issued.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenLifetimes {
+    refresh: Duration,
+    password_reset: Duration,
+    mfa_challenge: Duration,
+}
+
+impl TokenLifetimes {
+    pub fn new(refresh: Duration, password_reset: Duration, mfa_challenge: Duration) -> Self {
+        Self {
+            refresh,
+            password_reset,
+            mfa_challenge,
+        }
+    }
+    fn ttl(&self, purpose: TokenPurpose) -> Duration {
+        match purpose {
+            TokenPurpose::Refresh => self.refresh,
+            TokenPurpose::PasswordReset => self.password_reset,
+            TokenPurpose::MfaChallenge => self.mfa_challenge,
+        }
+    }
+}
+
+/// Where a token was requested from, recorded for the session overview.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn new(user_agent: Option<String>, ip_address: Option<String>) -> Self {
+        Self {
+            user_agent,
+            ip_address,
+        }
+    }
+    pub fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_deref()
+    }
+}
 
 #[derive(Debug)]
 pub struct Token {
     id: Uuid,
-    key: String,
+    /// Only known right after creation, the database stores `key_hash`.
+    key: Option<String>,
+    key_hash: String,
     user_id: Uuid,
+    family_id: Uuid,
+    purpose: TokenPurpose,
     expire_at: SystemTime,
     is_revoked: bool,
+    created_at: SystemTime,
+    last_used_at: SystemTime,
+    client_info: ClientInfo,
 }
 
 impl Token {
-    pub fn new(user_id: Uuid) -> Self {
-        let mut rng = thread_rng();
-        let key = (0..32)
-            .map(|_| rng.gen_range(0x0020..0x007E)) // UTF-8 characters in printable ASCII range
-            .map(|c| char::from_u32(c).unwrap())
-            .collect();
+    /// Creates the first token of a new family, i.e. a new login session.
+    pub fn new(user_id: Uuid, client_info: ClientInfo, lifetimes: &TokenLifetimes) -> Self {
+        Self::with_family(
+            user_id,
+            Uuid::now_v7(),
+            TokenPurpose::Refresh,
+            SystemTime::now(),
+            client_info,
+            lifetimes,
+        )
+    }
+    /// Creates a short-lived, single-use token that authorizes a password reset.
+    pub fn password_reset(user_id: Uuid, lifetimes: &TokenLifetimes) -> Self {
+        Self::with_family(
+            user_id,
+            Uuid::now_v7(),
+            TokenPurpose::PasswordReset,
+            SystemTime::now(),
+            ClientInfo::default(),
+            lifetimes,
+        )
+    }
+    /// Creates a short-lived, single-use token that proves the password step of
+    /// a two-factor login succeeded.
+    pub fn mfa_challenge(user_id: Uuid, client_info: ClientInfo, lifetimes: &TokenLifetimes) -> Self {
+        Self::with_family(
+            user_id,
+            Uuid::now_v7(),
+            TokenPurpose::MfaChallenge,
+            SystemTime::now(),
+            client_info,
+            lifetimes,
+        )
+    }
+    fn with_family(
+        user_id: Uuid,
+        family_id: Uuid,
+        purpose: TokenPurpose,
+        created_at: SystemTime,
+        client_info: ClientInfo,
+        lifetimes: &TokenLifetimes,
+    ) -> Self {
+        let mut bytes = [0u8; TOKEN_KEY_BYTES];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .expect("system random number generator failed");
+        let key = URL_SAFE_NO_PAD.encode(bytes);
 
         Self {
             id: Uuid::now_v7(),
-            key,
+            key_hash: Token::hash_key(&key),
+            key: Some(key),
             user_id,
-            expire_at: SystemTime::now() + TOKEN_TTL,
+            family_id,
+            purpose,
+            expire_at: SystemTime::now() + lifetimes.ttl(purpose),
             is_revoked: false,
+            created_at,
+            last_used_at: SystemTime::now(),
+            client_info,
         }
     }
     pub fn from_dto(token_dto: &TokenDto) -> Self {
         Self {
             id: *token_dto.id(),
-            key: token_dto.key().to_owned(),
+            key: token_dto.key().map(str::to_owned),
+            key_hash: token_dto.key_hash().to_owned(),
             user_id: *token_dto.user_id(),
+            family_id: *token_dto.family_id(),
+            purpose: token_dto.purpose(),
             expire_at: *token_dto.expire_at(),
             is_revoked: *token_dto.is_revoked(),
+            created_at: *token_dto.created_at(),
+            last_used_at: *token_dto.last_used_at(),
+            client_info: ClientInfo::new(
+                token_dto.user_agent().map(str::to_owned),
+                token_dto.ip_address().map(str::to_owned),
+            ),
         }
     }
     pub fn to_dto(&self) -> TokenDto {
-        TokenDto::new(
-            self.id,
-            self.key.to_owned(),
+        TokenDto {
+            id: self.id,
+            key: self.key.to_owned(),
+            key_hash: self.key_hash.to_owned(),
+            user_id: self.user_id,
+            family_id: self.family_id,
+            purpose: self.purpose,
+            expire_at: self.expire_at,
+            is_revoked: self.is_revoked,
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+            user_agent: self.client_info.user_agent.to_owned(),
+            ip_address: self.client_info.ip_address.to_owned(),
+        }
+    }
+    /// Creates the successor of this token within the same family. The
+    /// successor keeps the session start but records the latest use.
+    pub fn rotate(&self, client_info: ClientInfo, lifetimes: &TokenLifetimes) -> Self {
+        Self::with_family(
             self.user_id,
-            self.expire_at,
-            self.is_revoked,
+            self.family_id,
+            self.purpose,
+            self.created_at,
+            client_info,
+            lifetimes,
         )
     }
     pub fn validate(&self) -> Result<(), AuthenticationError> {
@@ -61,55 +235,117 @@ impl Token {
     pub fn revoke(&mut self) {
         self.is_revoked = true;
     }
+    pub fn is_revoked(&self) -> bool {
+        self.is_revoked
+    }
+    pub fn family_id(&self) -> &Uuid {
+        &self.family_id
+    }
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+    pub fn purpose(&self) -> TokenPurpose {
+        self.purpose
+    }
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+    pub fn client_info(&self) -> &ClientInfo {
+        &self.client_info
+    }
+    pub fn is_active(&self) -> bool {
+        self.validate().is_ok()
+    }
     pub fn matches(&self, key: &str) -> bool {
-        self.key == key
+        Token::key_matches_hash(key, &self.key_hash)
+    }
+    /// Checks `key` against a hash of `hash_key` in constant time.
+    pub fn key_matches_hash(key: &str, key_hash: &str) -> bool {
+        URL_SAFE_NO_PAD
+            .decode(key_hash)
+            .is_ok_and(|tag| hmac::verify(pepper(), key.as_bytes(), &tag).is_ok())
+    }
+    pub fn hash_key(key: &str) -> String {
+        URL_SAFE_NO_PAD.encode(hmac::sign(pepper(), key.as_bytes()))
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct TokenDto {
     id: Uuid,
-    key: String,
+    key: Option<String>,
+    key_hash: String,
     user_id: Uuid,
+    family_id: Uuid,
+    purpose: TokenPurpose,
     expire_at: SystemTime,
     is_revoked: bool,
+    created_at: SystemTime,
+    last_used_at: SystemTime,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
 }
 
 impl TokenDto {
-    fn new(id: Uuid, key: String, user_id: Uuid, expire_at: SystemTime, is_revoked: bool) -> Self {
-        Self {
-            id,
-            key,
-            user_id,
-            expire_at,
-            is_revoked,
-        }
-    }
     pub fn id(&self) -> &Uuid {
         &self.id
     }
-    pub fn key(&self) -> &str {
-        &self.key
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+    pub fn key_hash(&self) -> &str {
+        &self.key_hash
     }
     pub fn user_id(&self) -> &Uuid {
         &self.user_id
     }
+    pub fn family_id(&self) -> &Uuid {
+        &self.family_id
+    }
+    pub fn purpose(&self) -> TokenPurpose {
+        self.purpose
+    }
+    pub fn purpose_name(&self) -> &str {
+        self.purpose.as_str()
+    }
     pub fn expire_at(&self) -> &SystemTime {
         &self.expire_at
     }
     pub fn is_revoked(&self) -> &bool {
         &self.is_revoked
     }
+    pub fn created_at(&self) -> &SystemTime {
+        &self.created_at
+    }
+    pub fn last_used_at(&self) -> &SystemTime {
+        &self.last_used_at
+    }
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+    pub fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_deref()
+    }
 }
 
 impl From<&Row> for TokenDto {
     fn from(value: &Row) -> Self {
         Self {
             id: value.get(0),
-            key: value.get(1),
+            key: None,
+            key_hash: value.get(1),
             user_id: value.get(2),
             expire_at: value.get(3),
             is_revoked: value.get(4),
+            family_id: value.get(5),
+            created_at: value.get(6),
+            last_used_at: value.get(7),
+            user_agent: value.get(8),
+            ip_address: value.get(9),
+            purpose: value
+                .get::<_, &str>(10)
+                .parse()
+                .unwrap_or(TokenPurpose::Refresh),
         }
     }
 }
@@ -1,96 +1,336 @@
-use bcrypt::{hash, verify};
-use log::debug;
-use serde::Serialize;
+use std::time::SystemTime;
+
+use log::{debug, warn};
 use tokio_postgres::Row;
 use uuid::Uuid;
 
-use crate::core::error::AuthenticationError;
-use crate::core::token::{Token, TokenDto};
+use crate::core::error::{AuthenticationError, ThrottledError};
+use crate::core::lockout::LockoutPolicy;
+use crate::core::mfa::{Mfa, MfaDto};
+use crate::core::password::PasswordHasher;
+use crate::core::role::Role;
+use crate::core::token::{ClientInfo, Token, TokenDto, TokenLifetimes, TokenPurpose};
 
 #[derive(Debug)]
 pub struct User {
     id: Uuid,
     username: String,
     password: String,
+    roles: Vec<Role>,
     tokens: Vec<Token>,
+    mfa: Option<Mfa>,
+    failed_login_attempts: u32,
+    locked_until: Option<SystemTime>,
+    /// Version of the stored row this user was loaded from, an update of a
+    /// stale version is rejected instead of overwriting a concurrent one.
+    version: i64,
+}
+
+pub enum LoginOutcome {
+    Authenticated,
+    /// The password was correct, the key of the issued challenge token has to
+    /// be presented together with a second factor.
+    MfaRequired(String),
 }
 
 impl User {
-    pub fn new(username: String, password: String) -> Self {
+    pub async fn new(username: String, password: String, hasher: &dyn PasswordHasher) -> Self {
         Self {
             id: Uuid::now_v7(),
             username,
-            password: hash(password, 12).unwrap(),
+            password: hasher.hash(&password).await,
+            roles: vec![Role::User],
             tokens: Vec::with_capacity(1),
+            mfa: None,
+            failed_login_attempts: 0,
+            locked_until: None,
+            version: 0,
         }
     }
-    pub fn from_dto(user_dto: &UserDto, list_of_token_dto: &[TokenDto]) -> Self {
+    pub fn from_dto(
+        user_dto: &UserDto,
+        list_of_token_dto: &[TokenDto],
+        mfa_dto: Option<&MfaDto>,
+    ) -> Self {
         Self {
             id: *user_dto.id(),
             username: user_dto.username().to_owned(),
             password: user_dto.password().to_owned(),
+            roles: user_dto.roles().to_vec(),
             tokens: list_of_token_dto.iter().map(Token::from_dto).collect(),
+            mfa: mfa_dto.map(Mfa::from_dto),
+            failed_login_attempts: *user_dto.failed_login_attempts(),
+            locked_until: *user_dto.locked_until(),
+            version: *user_dto.version(),
         }
     }
     pub fn to_dto(&self) -> UserDto {
-        UserDto::new(
-            self.id,
-            self.username.to_owned(),
-            self.password.to_owned(),
-            self.tokens
+        UserDto {
+            id: self.id,
+            username: self.username.to_owned(),
+            password: self.password.to_owned(),
+            roles: self.roles.to_owned(),
+            tokens: self
+                .tokens
                 .iter()
                 .map(|token| token.to_dto())
                 .collect::<Vec<_>>(),
-        )
+            mfa: self.mfa.as_ref().map(Mfa::to_dto),
+            failed_login_attempts: self.failed_login_attempts,
+            locked_until: self.locked_until,
+            version: self.version,
+        }
     }
-    pub fn login(&mut self, password: &str) -> Result<(), AuthenticationError> {
-        if verify(password, self.password.as_str()).unwrap() {
-            let refresh_token = Token::new(self.id);
-            self.tokens.push(refresh_token);
-            return Ok(());
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+    /// Fails while the account is locked, before any password is verified.
+    pub fn check_lockout(&self) -> Result<(), ThrottledError> {
+        let remaining = self
+            .locked_until
+            .and_then(|locked_until| locked_until.duration_since(SystemTime::now()).ok());
+        if let Some(remaining) = remaining {
+            return Err(ThrottledError::AccountLocked { retry_after: remaining });
+        }
+        Ok(())
+    }
+    pub async fn login(
+        &mut self,
+        password: &str,
+        client_info: ClientInfo,
+        lockout_policy: &LockoutPolicy,
+        token_lifetimes: &TokenLifetimes,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<LoginOutcome, AuthenticationError> {
+        if !hasher.verify(password, &self.password).await {
+            self.record_failed_login(lockout_policy);
+            return Err(AuthenticationError::new("invalid credentials"));
+        }
+        if hasher.needs_rehash(&self.password) {
+            debug!("User.login() upgrading password hash of user_id={:?}", self.id);
+            self.password = hasher.hash(password).await;
+        }
+        // the failure count is only reset once the second factor was verified as well
+        if self.mfa.as_ref().is_some_and(Mfa::is_confirmed) {
+            let challenge = Token::mfa_challenge(self.id, client_info, token_lifetimes);
+            let key = challenge.key().expect("a new token knows its key").to_owned();
+            self.tokens.push(challenge);
+            return Ok(LoginOutcome::MfaRequired(key));
         }
-        Err(AuthenticationError::new("invalid credentials"))
+        self.clear_failed_logins();
+        let refresh_token = Token::new(self.id, client_info, token_lifetimes);
+        self.tokens.push(refresh_token);
+        Ok(LoginOutcome::Authenticated)
     }
-    pub fn refresh(&mut self, token_key: &str) -> Result<(), AuthenticationError> {
+    /// Second step of a two-factor login. The challenge is consumed even if the
+    /// code is wrong, so guessing codes requires the password every time.
+    pub fn complete_mfa_login(
+        &mut self,
+        challenge_key: &str,
+        code: &str,
+        lockout_policy: &LockoutPolicy,
+        token_lifetimes: &TokenLifetimes,
+    ) -> Result<(), AuthenticationError> {
+        let challenge = self
+            .token_by_key(challenge_key, TokenPurpose::MfaChallenge)
+            .ok_or(AuthenticationError::new("invalid challenge"))?;
+        challenge.validate()?;
+        challenge.revoke();
+        let client_info = challenge.client_info().clone();
+        let mfa = self
+            .mfa
+            .as_mut()
+            .filter(|mfa| mfa.is_confirmed())
+            .ok_or(AuthenticationError::new("two-factor authentication is not enabled"))?;
+        if !mfa.verify(code) {
+            self.record_failed_login(lockout_policy);
+            return Err(AuthenticationError::new("invalid code"));
+        }
+        self.clear_failed_logins();
+        let refresh_token = Token::new(self.id, client_info, token_lifetimes);
+        self.tokens.push(refresh_token);
+        Ok(())
+    }
+    /// Starts (or restarts) an enrollment, returns the base32 secret and the
+    /// matching `otpauth://` URI.
+    pub fn enroll_mfa(&mut self) -> Result<(String, String), AuthenticationError> {
+        if self.mfa.as_ref().is_some_and(Mfa::is_confirmed) {
+            return Err(AuthenticationError::new("two-factor authentication is already enabled"));
+        }
+        let mfa = Mfa::generate();
+        let enrollment = (mfa.secret_base32(), mfa.otpauth_uri(&self.username));
+        self.mfa = Some(mfa);
+        Ok(enrollment)
+    }
+    pub fn confirm_mfa(&mut self, code: &str) -> Result<Vec<String>, AuthenticationError> {
+        self.mfa
+            .as_mut()
+            .ok_or(AuthenticationError::new("two-factor authentication is not enrolled"))?
+            .confirm(code)
+    }
+    pub fn refresh(
+        &mut self,
+        token_key: &str,
+        client_info: ClientInfo,
+        token_lifetimes: &TokenLifetimes,
+    ) -> Result<(), AuthenticationError> {
         debug!("User.refresh() with inputs: token_key={:?}", token_key);
-        if let Some(old_token) = self.token_by_key(token_key) {
+        let user_id = self.id;
+        if let Some(old_token) = self.token_by_key(token_key, TokenPurpose::Refresh) {
+            if old_token.is_revoked() {
+                let family_id = *old_token.family_id();
+                warn!(
+                    target: "security",
+                    "refresh token reuse detected for user_id={:?}, revoking token family family_id={:?}",
+                    user_id,
+                    family_id
+                );
+                self.revoke_family(&family_id);
+                return Err(AuthenticationError::new("invalid token"));
+            }
             old_token.validate()?;
             old_token.revoke();
-            let new_token = Token::new(self.id);
+            let new_token = old_token.rotate(client_info, token_lifetimes);
             self.tokens.push(new_token);
             return Ok(());
         }
         Err(AuthenticationError::new("invalid token"))
     }
     pub fn logout(&mut self, token_key: &str) -> Result<(), AuthenticationError> {
-        if let Some(old_token) = self.token_by_key(token_key) {
+        if let Some(old_token) = self.token_by_key(token_key, TokenPurpose::Refresh) {
             old_token.revoke();
             return Ok(());
         }
         Err(AuthenticationError::new("invalid token"))
     }
-    fn token_by_key(&mut self, key: &str) -> Option<&mut Token> {
-        self.tokens.iter_mut().find(|token| token.matches(key))
+    pub fn sessions(&self) -> Vec<TokenDto> {
+        self.tokens
+            .iter()
+            .filter(|token| token.purpose() == TokenPurpose::Refresh && token.is_active())
+            .map(Token::to_dto)
+            .collect()
+    }
+    /// Revokes the family of the active token with the given id, returns
+    /// `false` if the user has no such session.
+    pub fn revoke_session(&mut self, session_id: &Uuid) -> bool {
+        let family_id = self
+            .tokens
+            .iter()
+            .find(|token| token.id() == session_id && token.is_active())
+            .map(|token| *token.family_id());
+        if let Some(family_id) = family_id {
+            self.revoke_family(&family_id);
+            return true;
+        }
+        false
+    }
+    pub fn logout_all(&mut self) {
+        self.tokens.iter_mut().for_each(Token::revoke);
+    }
+    /// Changes the password and revokes every session except the one the
+    /// request was made from.
+    pub async fn change_password(
+        &mut self,
+        old_password: &str,
+        new_password: &str,
+        current_token_key: Option<&str>,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(), AuthenticationError> {
+        if !hasher.verify(old_password, &self.password).await {
+            return Err(AuthenticationError::new("invalid credentials"));
+        }
+        self.password = hasher.hash(new_password).await;
+        let current_family_id = current_token_key
+            .and_then(|key| self.token_by_key(key, TokenPurpose::Refresh))
+            .map(|token| *token.family_id());
+        self.tokens
+            .iter_mut()
+            .filter(|token| Some(*token.family_id()) != current_family_id)
+            .for_each(Token::revoke);
+        Ok(())
+    }
+    /// Issues a new password reset token, invalidating any earlier one, and
+    /// returns its key for delivery to the user.
+    pub fn request_password_reset(&mut self, token_lifetimes: &TokenLifetimes) -> String {
+        self.tokens
+            .iter_mut()
+            .filter(|token| token.purpose() == TokenPurpose::PasswordReset)
+            .for_each(Token::revoke);
+        let reset_token = Token::password_reset(self.id, token_lifetimes);
+        let key = reset_token
+            .key()
+            .expect("a new token knows its key")
+            .to_owned();
+        self.tokens.push(reset_token);
+        key
+    }
+    pub async fn reset_password(
+        &mut self,
+        token_key: &str,
+        new_password: &str,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(), AuthenticationError> {
+        let reset_token = self
+            .token_by_key(token_key, TokenPurpose::PasswordReset)
+            .ok_or(AuthenticationError::new("invalid token"))?;
+        reset_token.validate()?;
+        reset_token.revoke();
+        self.password = hasher.hash(new_password).await;
+        self.clear_failed_logins();
+        self.logout_all();
+        Ok(())
+    }
+    pub fn grant(&mut self, role: Role) {
+        if !self.roles.contains(&role) {
+            self.roles.push(role);
+        }
+    }
+    fn record_failed_login(&mut self, lockout_policy: &LockoutPolicy) {
+        self.failed_login_attempts += 1;
+        if let Some(delay) = lockout_policy.delay(self.failed_login_attempts) {
+            warn!(
+                target: "security",
+                "locking user_id={:?} for {:?} after {} failed login attempts",
+                self.id,
+                delay,
+                self.failed_login_attempts
+            );
+            self.locked_until = Some(SystemTime::now() + delay);
+        }
+    }
+    fn clear_failed_logins(&mut self) {
+        self.failed_login_attempts = 0;
+        self.locked_until = None;
+    }
+    fn revoke_family(&mut self, family_id: &Uuid) {
+        self.tokens
+            .iter_mut()
+            .filter(|token| token.family_id() == family_id)
+            .for_each(Token::revoke);
+    }
+    fn token_by_key(&mut self, key: &str, purpose: TokenPurpose) -> Option<&mut Token> {
+        self.tokens
+            .iter_mut()
+            .find(|token| token.purpose() == purpose && token.matches(key))
     }
 }
 
-#[derive(Serialize, Debug)]
+/// Internal transfer object, deliberately not `Serialize`: it carries the
+/// password hash and token keys. The api layer maps it to response models.
+#[derive(Clone, Debug)]
 pub struct UserDto {
     id: Uuid,
     username: String,
     password: String,
+    roles: Vec<Role>,
     tokens: Vec<TokenDto>,
+    mfa: Option<MfaDto>,
+    failed_login_attempts: u32,
+    locked_until: Option<SystemTime>,
+    version: i64,
 }
 
 impl UserDto {
-    fn new(id: Uuid, username: String, password: String, tokens: Vec<TokenDto>) -> Self {
-        Self {
-            id,
-            username,
-            password,
-            tokens,
-        }
-    }
     pub fn id(&self) -> &Uuid {
         &self.id
     }
@@ -100,12 +340,37 @@ impl UserDto {
     pub fn password(&self) -> &str {
         &self.password
     }
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+    pub fn role_names(&self) -> Vec<&str> {
+        self.roles.iter().map(Role::as_str).collect()
+    }
     pub fn tokens(&self) -> &Vec<TokenDto> {
         self.tokens.as_ref()
     }
+    pub fn mfa(&self) -> Option<&MfaDto> {
+        self.mfa.as_ref()
+    }
+    pub fn failed_login_attempts(&self) -> &u32 {
+        &self.failed_login_attempts
+    }
+    pub fn locked_until(&self) -> &Option<SystemTime> {
+        &self.locked_until
+    }
+    pub fn version(&self) -> &i64 {
+        &self.version
+    }
     pub fn latest_token(&self) -> Option<&TokenDto> {
         self.tokens.last()
     }
+    /// What the stored row looks like after this version was written.
+    pub fn with_next_version(self) -> Self {
+        Self {
+            version: self.version + 1,
+            ..self
+        }
+    }
 }
 
 impl From<&Row> for UserDto {
@@ -114,7 +379,16 @@ impl From<&Row> for UserDto {
             id: value.get(0),
             username: value.get(1),
             password: value.get(2),
+            roles: value
+                .get::<_, Vec<String>>(3)
+                .iter()
+                .filter_map(|role| role.parse().ok())
+                .collect(),
             tokens: Vec::new(),
+            mfa: None,
+            failed_login_attempts: value.get::<_, i32>(4) as u32,
+            locked_until: value.get(5),
+            version: value.get(6),
         }
     }
 }
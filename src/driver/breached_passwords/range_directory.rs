@@ -0,0 +1,45 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use data_encoding::HEXUPPER;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+
+use crate::business::error::BusinessError;
+use crate::business::user::breached_passwords::BreachedPasswords;
+use crate::driver::error::DriverError;
+
+const PREFIX_LENGTH: usize = 5;
+
+/// Breached password hashes split into range files the way the Pwned
+/// Passwords API serves them: the file `<PREFIX>.txt` holds one
+/// `SUFFIX:COUNT` line per SHA-1 hash starting with that five character
+/// prefix. Only the file of the password's prefix is read, nothing leaves
+/// the machine.
+#[derive(Debug)]
+pub struct RangeDirectory {
+    directory: PathBuf,
+}
+
+impl RangeDirectory {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+#[async_trait]
+impl BreachedPasswords for RangeDirectory {
+    async fn contains(&self, password: &str) -> Result<bool, BusinessError> {
+        let hash = HEXUPPER.encode(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()).as_ref());
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+        let range = match tokio::fs::read_to_string(self.directory.join(format!("{prefix}.txt"))).await {
+            Ok(range) => range,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(DriverError::from(err).into()),
+        };
+        Ok(range
+            .lines()
+            .filter_map(|line| line.split(':').next())
+            .any(|candidate| candidate.trim().eq_ignore_ascii_case(suffix)))
+    }
+}
@@ -0,0 +1,53 @@
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::driver::error::DriverError;
+
+/// Encrypts secrets at rest with AES-256-GCM. The random nonce is stored in
+/// front of the ciphertext.
+pub struct SecretCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl SecretCipher {
+    pub fn new(key: &[u8]) -> Result<Self, DriverError> {
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| DriverError::Crypto("encryption key must be 32 bytes long"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, DriverError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| DriverError::Crypto("could not generate nonce"))?;
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out)
+            .map_err(|_| DriverError::Crypto("could not encrypt secret"))?;
+        Ok([nonce.as_slice(), &in_out].concat())
+    }
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, DriverError> {
+        if ciphertext.len() < NONCE_LEN {
+            return Err(DriverError::Crypto("ciphertext is too short"));
+        }
+        let (nonce, sealed) = ciphertext.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| DriverError::Crypto("invalid nonce"))?;
+        let mut in_out = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| DriverError::Crypto("could not decrypt secret"))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+impl std::fmt::Debug for SecretCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretCipher").finish_non_exhaustive()
+    }
+}
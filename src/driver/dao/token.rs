@@ -1,76 +1,102 @@
-use std::sync::Arc;
-
 use log::debug;
 use tokio_postgres::types::ToSql;
 use uuid::Uuid;
 
 use crate::core::token::TokenDto;
-use crate::driver::database::client_adapter::ClientAdapter;
-use crate::driver::database::pool_adapter::PoolAdapter;
+use crate::driver::database::client_adapter::{CachingClient, ClientAdapter};
 use crate::driver::error::DriverError;
 
 #[derive(Debug)]
-pub struct TokenDao {
-    pool: Arc<PoolAdapter>,
-}
+pub struct TokenDao;
 
 impl TokenDao {
-    pub fn new(pool: Arc<PoolAdapter>) -> Self {
-        Self { pool }
-    }
-    pub async fn create(&self, token_dto: &TokenDto) -> Result<(), DriverError> {
+    pub async fn create(&self, client: &impl CachingClient, token_dto: &TokenDto) -> Result<(), DriverError> {
         debug!("TokenDao.create() with inputs: token_dto={:?}", token_dto);
-        let statement = "INSERT INTO Tokens VALUES ($1, $2, $3, $4, $5)";
-        let values: [&(dyn ToSql + Sync); 5] = [
+        let statement = r#"
+            INSERT INTO Tokens (
+                id, key_hash, user_id, expire_at, is_revoked, family_id,
+                created_at, last_used_at, user_agent, ip_address, purpose
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#;
+        let values: [&(dyn ToSql + Sync); 11] = [
             &token_dto.id(),
-            &token_dto.key(),
+            &token_dto.key_hash(),
             &token_dto.user_id(),
             &token_dto.expire_at(),
             &token_dto.is_revoked(),
+            &token_dto.family_id(),
+            &token_dto.created_at(),
+            &token_dto.last_used_at(),
+            &token_dto.user_agent(),
+            &token_dto.ip_address(),
+            &token_dto.purpose_name(),
         ];
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &values).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &values).await?;
         Ok(())
     }
-    pub async fn save(&self, token_dto: &TokenDto) -> Result<(), DriverError> {
+    pub async fn save(&self, client: &impl CachingClient, token_dto: &TokenDto) -> Result<(), DriverError> {
         debug!("TokenDao.save() with inputs: token_dto={:?}", token_dto);
         let statement = r#"
-            INSERT INTO Tokens VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO Tokens (
+                id, key_hash, user_id, expire_at, is_revoked, family_id,
+                created_at, last_used_at, user_agent, ip_address, purpose
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             ON CONFLICT (id) DO UPDATE
-            SET key = EXCLUDED.key,
+            SET key_hash = EXCLUDED.key_hash,
                 user_id = EXCLUDED.user_id,
                 expire_at = EXCLUDED.expire_at,
-                is_revoked = EXCLUDED.is_revoked
+                is_revoked = EXCLUDED.is_revoked,
+                family_id = EXCLUDED.family_id,
+                created_at = EXCLUDED.created_at,
+                last_used_at = EXCLUDED.last_used_at,
+                user_agent = EXCLUDED.user_agent,
+                ip_address = EXCLUDED.ip_address,
+                purpose = EXCLUDED.purpose
         "#;
-        let values: [&(dyn ToSql + Sync); 5] = [
+        let values: [&(dyn ToSql + Sync); 11] = [
             &token_dto.id(),
-            &token_dto.key(),
+            &token_dto.key_hash(),
             &token_dto.user_id(),
             &token_dto.expire_at(),
             &token_dto.is_revoked(),
+            &token_dto.family_id(),
+            &token_dto.created_at(),
+            &token_dto.last_used_at(),
+            &token_dto.user_agent(),
+            &token_dto.ip_address(),
+            &token_dto.purpose_name(),
         ];
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &values).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &values).await?;
         Ok(())
     }
-    pub async fn delete_by_user_id(&self, user_id: &Uuid) -> Result<(), DriverError> {
+    pub async fn delete_by_user_id(
+        &self,
+        client: &impl CachingClient,
+        user_id: &Uuid,
+    ) -> Result<(), DriverError> {
         debug!("TokenDao.delete_by_user_id() with inputs: user_id={user_id:?}");
         let statement = "DELETE FROM Tokens WHERE user_id=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &[&user_id]).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &[&user_id]).await?;
         Ok(())
     }
-    pub async fn find_by_user_id(&self, user_id: &Uuid) -> Result<Vec<TokenDto>, DriverError> {
-        debug!("TokenDao.find_by_user_id() with inputs: user_id={user_id:?}");
-        let statement = "SELECT * FROM Tokens WHERE user_id=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        let rows = ClientAdapter::query(&mut client, stmt, &[&user_id]).await?;
+    /// Loads the tokens of several users in one round trip, oldest first.
+    pub async fn find_by_user_ids(
+        &self,
+        client: &impl CachingClient,
+        user_ids: &[Uuid],
+    ) -> Result<Vec<TokenDto>, DriverError> {
+        debug!("TokenDao.find_by_user_ids() with inputs: user_ids={user_ids:?}");
+        // rotated tokens keep the created_at of their family, the time-ordered id breaks the tie
+        let statement = "SELECT * FROM Tokens WHERE user_id = ANY($1) ORDER BY created_at, id";
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &[&user_ids]).await?;
         let result = Ok(rows.into_iter().map(|row| TokenDto::from(&row)).collect());
-        debug!("TokenDao.find_by_user_id() with output: {:?}", result);
+        debug!("TokenDao.find_by_user_ids() with output: {:?}", result);
         result
     }
 }
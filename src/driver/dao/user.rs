@@ -4,87 +4,189 @@ use log::debug;
 use tokio_postgres::types::ToSql;
 use uuid::Uuid;
 
+use crate::core::mfa::MfaDto;
+use crate::core::page::SortOrder;
 use crate::core::user::UserDto;
-use crate::driver::database::client_adapter::ClientAdapter;
-use crate::driver::database::pool_adapter::PoolAdapter;
+use crate::driver::crypto::secret_cipher::SecretCipher;
+use crate::driver::database::client_adapter::{CachingClient, ClientAdapter};
 use crate::driver::error::DriverError;
 
+/// Runs on the caller's connection so a repository operation needs only one.
 #[derive(Debug)]
 pub struct UserDao {
-    pool: Arc<PoolAdapter>,
+    cipher: Arc<SecretCipher>,
 }
 
 impl UserDao {
-    pub fn new(pool: Arc<PoolAdapter>) -> Self {
-        Self { pool }
+    pub fn new(cipher: Arc<SecretCipher>) -> Self {
+        Self { cipher }
     }
-    pub async fn create(&self, user_dto: &UserDto) -> Result<(), DriverError> {
+    pub async fn create(&self, client: &impl CachingClient, user_dto: &UserDto) -> Result<(), DriverError> {
         debug!("UserDao.create() with inputs: user_dto={:?}", user_dto);
-        let statement = "INSERT INTO Users VALUES ($1, $2, $3)";
-        let values: [&(dyn ToSql + Sync); 3] =
-            [&user_dto.id(), &user_dto.username(), &user_dto.password()];
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &values).await?;
+        let statement = "INSERT INTO Users VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        let roles = user_dto.role_names();
+        let failed_login_attempts = *user_dto.failed_login_attempts() as i32;
+        let values: [&(dyn ToSql + Sync); 7] = [
+            &user_dto.id(),
+            &user_dto.username(),
+            &user_dto.password(),
+            &roles,
+            &failed_login_attempts,
+            user_dto.locked_until(),
+            user_dto.version(),
+        ];
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &values).await?;
         Ok(())
     }
-    pub async fn find_by_id(&self, id: &Uuid) -> Result<Option<UserDto>, DriverError> {
+    pub async fn find_by_id(&self, client: &impl CachingClient, id: &Uuid) -> Result<Option<UserDto>, DriverError> {
         debug!("UserDao.find_by_id() with inputs: id={:?}", id);
         let statement = "SELECT * FROM Users WHERE id=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        let rows = ClientAdapter::query(&mut client, stmt, &[id]).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &[id]).await?;
         let result = Ok(rows.first().map(UserDto::from));
         debug!("UserDao.find_by_id() with output: {:?}", result);
         result
     }
-    pub async fn find_by_username(&self, username: &str) -> Result<Option<UserDto>, DriverError> {
+    pub async fn find_by_username(
+        &self,
+        client: &impl CachingClient,
+        username: &str,
+    ) -> Result<Option<UserDto>, DriverError> {
         debug!("UserDao.find_by_username() with inputs: username={username:?}");
         let statement = "SELECT * FROM Users WHERE username=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        let rows = ClientAdapter::query(&mut client, stmt, &[&username]).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &[&username]).await?;
         let result = Ok(rows.first().map(UserDto::from));
         debug!("UserDao.find_by_username() with output: {:?}", result);
         result
     }
-    pub async fn find_by_token(&self, key: &str) -> Result<Option<UserDto>, DriverError> {
-        debug!("UserDao.find_by_token() with inputs: key={:?}", key);
+    pub async fn find_by_token(
+        &self,
+        client: &impl CachingClient,
+        key_hash: &str,
+    ) -> Result<Option<UserDto>, DriverError> {
+        debug!("UserDao.find_by_token() with inputs: key_hash={:?}", key_hash);
         let statement =
-            "SELECT u.* FROM Users u INNER JOIN Tokens t ON u.id = t.user_id WHERE t.key=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        let rows = ClientAdapter::query(&mut client, stmt, &[&key]).await?;
+            "SELECT u.* FROM Users u INNER JOIN Tokens t ON u.id = t.user_id WHERE t.key_hash=$1";
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &[&key_hash]).await?;
         let result = Ok(rows.first().map(UserDto::from));
         debug!("UserDao.find_by_token() with output: {:?}", result);
         result
     }
-    pub async fn find_all(&self) -> Result<Vec<UserDto>, DriverError> {
-        debug!("UserDao.find_all() with no inputs");
-        let statement = "SELECT * FROM Users";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        let rows = ClientAdapter::query(&mut client, stmt, &[]).await?;
+    /// Keyset pagination on the time-ordered id, so deep pages cost the same as the first.
+    pub async fn find_page(
+        &self,
+        client: &impl CachingClient,
+        username_prefix: Option<&str>,
+        cursor: Option<&Uuid>,
+        order: SortOrder,
+        limit: i64,
+    ) -> Result<Vec<UserDto>, DriverError> {
+        debug!(
+            "UserDao.find_page() with inputs: username_prefix={:?}, cursor={:?}, order={:?}, limit={:?}",
+            username_prefix, cursor, order, limit
+        );
+        let statement = match order {
+            SortOrder::Asc => r#"
+                SELECT * FROM Users
+                WHERE ($1::VARCHAR IS NULL OR starts_with(username, $1)) AND ($2::UUID IS NULL OR id > $2)
+                ORDER BY id ASC
+                LIMIT $3
+            "#,
+            SortOrder::Desc => r#"
+                SELECT * FROM Users
+                WHERE ($1::VARCHAR IS NULL OR starts_with(username, $1)) AND ($2::UUID IS NULL OR id < $2)
+                ORDER BY id DESC
+                LIMIT $3
+            "#,
+        };
+        let values: [&(dyn ToSql + Sync); 3] = [&username_prefix, &cursor, &limit];
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &values).await?;
         let result = Ok(rows.iter().map(UserDto::from).collect());
-        debug!("UserDao.find_all() with output: {:?}", result);
+        debug!("UserDao.find_page() with output: {:?}", result);
         result
     }
-    pub async fn update(&self, user_dto: &UserDto) -> Result<(), DriverError> {
+    /// Fails with `DriverError::StaleVersion` if the row is gone or was
+    /// updated since `user_dto` was loaded.
+    pub async fn update(&self, client: &impl CachingClient, user_dto: &UserDto) -> Result<(), DriverError> {
         debug!("UserDao.update() with inputs: user_dto={:?}", user_dto);
-        let statement = "UPDATE Users SET username=$2, password=$3 WHERE id=$1";
-        let values: [&(dyn ToSql + Sync); 3] =
-            [&user_dto.id(), &user_dto.username(), &user_dto.password()];
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &values).await?;
+        let statement = r#"
+            UPDATE Users
+            SET username=$2, password=$3, roles=$4, failed_login_attempts=$5, locked_until=$6, version=version + 1
+            WHERE id=$1 AND version=$7
+        "#;
+        let roles = user_dto.role_names();
+        let failed_login_attempts = *user_dto.failed_login_attempts() as i32;
+        let values: [&(dyn ToSql + Sync); 7] = [
+            &user_dto.id(),
+            &user_dto.username(),
+            &user_dto.password(),
+            &roles,
+            &failed_login_attempts,
+            user_dto.locked_until(),
+            user_dto.version(),
+        ];
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        if ClientAdapter::execute(client, stmt, &values).await? == 0 {
+            return Err(DriverError::StaleVersion(*user_dto.id()));
+        }
         Ok(())
     }
-    pub async fn delete_by_id(&self, id: &Uuid) -> Result<(), DriverError> {
+    pub async fn delete_by_id(&self, client: &impl CachingClient, id: &Uuid) -> Result<(), DriverError> {
         debug!("UserDao.delete_by_id() with inputs: id={:?}", id);
         let statement = "DELETE FROM Users WHERE id=$1";
-        let mut client = self.pool.get_connection().await?;
-        let stmt = ClientAdapter::prepare(&mut client, statement).await?;
-        ClientAdapter::execute(&mut client, stmt, &[&id]).await?;
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &[&id]).await?;
+        Ok(())
+    }
+    /// Loads the MFA credentials of several users in one round trip, keyed by user id.
+    pub async fn find_mfa_by_user_ids(
+        &self,
+        client: &impl CachingClient,
+        user_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, MfaDto)>, DriverError> {
+        debug!("UserDao.find_mfa_by_user_ids() with inputs: user_ids={:?}", user_ids);
+        let statement = "SELECT * FROM MfaCredentials WHERE user_id = ANY($1)";
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        let rows = ClientAdapter::query(client, stmt, &[&user_ids]).await?;
+        let result = rows
+            .iter()
+            .map(|row| {
+                let mfa_dto = MfaDto::new(self.cipher.decrypt(row.get(1))?, row.get(2), row.get(3), row.get(4));
+                Ok((row.get(0), mfa_dto))
+            })
+            .collect();
+        debug!("UserDao.find_mfa_by_user_ids() with output: {:?}", result);
+        result
+    }
+    pub async fn save_mfa(
+        &self,
+        client: &impl CachingClient,
+        user_id: &Uuid,
+        mfa_dto: &MfaDto,
+    ) -> Result<(), DriverError> {
+        debug!("UserDao.save_mfa() with inputs: user_id={:?}, mfa_dto={:?}", user_id, mfa_dto);
+        let statement = r#"
+            INSERT INTO MfaCredentials VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret,
+                is_confirmed = EXCLUDED.is_confirmed,
+                recovery_codes = EXCLUDED.recovery_codes,
+                last_used_step = EXCLUDED.last_used_step
+        "#;
+        let secret = self.cipher.encrypt(mfa_dto.secret())?;
+        let values: [&(dyn ToSql + Sync); 5] = [
+            user_id,
+            &secret,
+            mfa_dto.is_confirmed(),
+            &mfa_dto.recovery_code_hashes(),
+            mfa_dto.last_used_step(),
+        ];
+        let stmt = ClientAdapter::prepare(client, statement).await?;
+        ClientAdapter::execute(client, stmt, &values).await?;
         Ok(())
     }
 }
@@ -1,33 +1,85 @@
-use deadpool_postgres::Object as Client;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use deadpool_postgres::{GenericClient, Object, StatementCache, Transaction};
 use tokio_postgres::{Row, Statement};
 use tokio_postgres::types::ToSql;
 
 use crate::driver::error::DriverError;
 
+static STATEMENT_CACHE_METRICS: StatementCacheMetrics = StatementCacheMetrics {
+    hits: AtomicU64::new(0),
+    misses: AtomicU64::new(0),
+};
+
+/// Process-wide counters of statement cache lookups over all connections.
+#[derive(Debug)]
+pub struct StatementCacheMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StatementCacheMetrics {
+    pub fn global() -> &'static Self {
+        &STATEMENT_CACHE_METRICS
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+    fn record(&self, is_hit: bool) {
+        let counter = if is_hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A pooled connection or a transaction on one, both share the statement
+/// cache of the underlying connection.
+pub trait CachingClient: GenericClient {
+    fn statement_cache(&self) -> &StatementCache;
+}
+
+impl CachingClient for Object {
+    fn statement_cache(&self) -> &StatementCache {
+        &self.statement_cache
+    }
+}
+
+impl CachingClient for Transaction<'_> {
+    fn statement_cache(&self) -> &StatementCache {
+        &self.statement_cache
+    }
+}
+
 pub struct ClientAdapter;
 
 impl ClientAdapter {
-    pub async fn prepare(client: &mut Client, statement: &str) -> Result<Statement, DriverError> {
-        client
-            .prepare(statement)
+    /// Prepares a statement once per connection, later calls are answered
+    /// from the connection's cache without a round trip to Postgres.
+    pub async fn prepare(client: &impl CachingClient, statement: &str) -> Result<Statement, DriverError> {
+        let cached_statements = client.statement_cache().size();
+        let stmt = client
+            .prepare_cached(statement)
             .await
-            .map_err(DriverError::from)
+            .map_err(DriverError::from)?;
+        StatementCacheMetrics::global().record(client.statement_cache().size() == cached_statements);
+        Ok(stmt)
     }
 
     pub async fn execute(
-        client: &mut Client,
+        client: &impl CachingClient,
         stmt: Statement,
         values: &[&(dyn ToSql + Sync)],
-    ) -> Result<(), DriverError> {
+    ) -> Result<u64, DriverError> {
         client
             .execute(&stmt, values)
             .await
-            .map_err(DriverError::from)?;
-        Ok(())
+            .map_err(DriverError::from)
     }
 
     pub async fn query(
-        client: &mut Client,
+        client: &impl CachingClient,
         stmt: Statement,
         values: &[&(dyn ToSql + Sync)],
     ) -> Result<Vec<Row>, DriverError> {
@@ -1,14 +1,110 @@
-use deadpool_postgres::Config;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub struct ConfigFactory;
+use deadpool_postgres::{Config, PoolConfig, SslMode as PgSslMode, Timeouts};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::driver::database::tls_factory::TlsFactory;
+use crate::driver::error::DriverError;
+use crate::settings::{DatabaseSettings, SslMode};
+
+pub struct ConfigFactory {
+    settings: DatabaseSettings,
+}
+
+/// The libpq parameters of a url that tokio-postgres rejects, or in the case
+/// of `sslmode` only understands in part.
+#[derive(Default)]
+struct UrlTls {
+    ssl_mode: Option<SslMode>,
+    root_cert: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
 
 impl ConfigFactory {
-    pub fn create(&self) -> Config {
+    pub fn new(settings: DatabaseSettings) -> Self {
+        Self { settings }
+    }
+    /// Fails when the settings do not make up a complete connection config,
+    /// e.g. on a malformed url or without a database name.
+    pub fn create(&self) -> Result<Config, DriverError> {
+        let (url, url_tls) = self.url()?;
         let mut cfg = Config::new();
-        cfg.host = std::env::var("PG_HOST").ok();
-        cfg.dbname = std::env::var("PG_DBNAME").ok();
-        cfg.user = std::env::var("PG_USER").ok();
-        cfg.password = std::env::var("PG_PASSWORD").ok();
-        cfg
+        cfg.url = url;
+        cfg.host = self.settings.host.to_owned();
+        cfg.port = self.settings.port;
+        cfg.dbname = self.settings.dbname.to_owned();
+        cfg.user = self.settings.user.to_owned();
+        cfg.password = self.settings.password.as_ref().map(|password| password.expose().to_owned());
+        // certificates are checked by the connector, tokio-postgres only needs to know whether to use it
+        cfg.ssl_mode = Some(match self.ssl_mode(&url_tls) {
+            SslMode::Disable => PgSslMode::Disable,
+            SslMode::Prefer => PgSslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => PgSslMode::Require,
+        });
+        cfg.connect_timeout = self.settings.connect_timeout_secs.map(Duration::from_secs);
+
+        let pool = &self.settings.pool;
+        let mut pool_config = pool.max_size.map_or_else(PoolConfig::default, PoolConfig::new);
+        pool_config.timeouts = Timeouts {
+            wait: pool.wait_timeout_secs.map(Duration::from_secs),
+            create: pool.create_timeout_secs.map(Duration::from_secs),
+            recycle: pool.recycle_timeout_secs.map(Duration::from_secs),
+        };
+        cfg.pool = Some(pool_config);
+
+        cfg.get_pg_config().map_err(|err| DriverError::Config(err.to_string()))?;
+        Ok(cfg)
+    }
+    /// Reads the certificate files, so it fails on missing or malformed ones.
+    pub fn create_tls(&self) -> Result<MakeRustlsConnect, DriverError> {
+        let (_, url_tls) = self.url()?;
+        let ssl_mode = self.ssl_mode(&url_tls);
+        let root_cert = self.settings.ssl_root_cert.clone().or(url_tls.root_cert);
+        let client_identity = match (
+            self.settings.ssl_cert.clone().or(url_tls.cert),
+            self.settings.ssl_key.clone().or(url_tls.key),
+        ) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => return Err(DriverError::Config("ssl_cert and ssl_key must be set together".to_owned())),
+        };
+        TlsFactory::new(ssl_mode, root_cert, client_identity).create()
+    }
+    fn ssl_mode(&self, url_tls: &UrlTls) -> SslMode {
+        self.settings.ssl_mode.or(url_tls.ssl_mode).unwrap_or(SslMode::Prefer)
+    }
+    /// Takes the TLS parameters out of the url, tokio-postgres gets the rest.
+    fn url(&self) -> Result<(Option<String>, UrlTls), DriverError> {
+        let mut url_tls = UrlTls::default();
+        let Some(url) = &self.settings.url else {
+            return Ok((None, url_tls));
+        };
+        let Some((base, query)) = url.expose().split_once('?') else {
+            return Ok((Some(url.expose().to_owned()), url_tls));
+        };
+        let mut parameters = Vec::new();
+        for parameter in query.split('&') {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let value = || {
+                urlencoding::decode(value)
+                    .map(|value| value.into_owned())
+                    .map_err(|err| DriverError::Config(format!("url parameter {name}: {err}")))
+            };
+            match name {
+                "sslmode" => url_tls.ssl_mode = Some(value()?.parse().map_err(DriverError::Config)?),
+                "sslrootcert" => url_tls.root_cert = Some(PathBuf::from(value()?)),
+                "sslcert" => url_tls.cert = Some(PathBuf::from(value()?)),
+                "sslkey" => url_tls.key = Some(PathBuf::from(value()?)),
+                _ => parameters.push(parameter),
+            }
+        }
+        let url = if parameters.is_empty() {
+            base.to_owned()
+        } else {
+            format!("{base}?{}", parameters.join("&"))
+        };
+        Ok((Some(url), url_tls))
     }
 }
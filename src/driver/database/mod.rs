@@ -2,3 +2,5 @@ pub mod client_adapter;
 pub mod config_factory;
 pub mod pool_adapter;
 pub mod pool_factory;
+pub mod tls_factory;
+pub mod unit_of_work;
@@ -1,5 +1,4 @@
 use deadpool_postgres::{Pool, Runtime};
-use tokio_postgres::NoTls;
 use tokio_postgres_migration::Migration;
 
 use crate::driver::database::config_factory::ConfigFactory;
@@ -8,9 +7,18 @@ pub struct PoolFactory {
     config_factory: ConfigFactory,
 }
 
-const SCRIPTS_UP: [(&str, &str); 2] = [
+const SCRIPTS_UP: [(&str, &str); 11] = [
     ("0001_create-users", include_str!("../../../migrations/0001_create-users_up.sql")),
     ("0002_create-tokens", include_str!("../../../migrations/0002_create-tokens_up.sql")),
+    ("0003_add-roles-to-users", include_str!("../../../migrations/0003_add-roles-to-users_up.sql")),
+    ("0004_add-family-to-tokens", include_str!("../../../migrations/0004_add-family-to-tokens_up.sql")),
+    ("0005_revoke-plaintext-tokens", include_str!("../../../migrations/0005_revoke-plaintext-tokens_up.sql")),
+    ("0006_rename-token-key-to-key-hash", include_str!("../../../migrations/0006_rename-token-key-to-key-hash_up.sql")),
+    ("0007_add-session-info-to-tokens", include_str!("../../../migrations/0007_add-session-info-to-tokens_up.sql")),
+    ("0008_add-purpose-to-tokens", include_str!("../../../migrations/0008_add-purpose-to-tokens_up.sql")),
+    ("0009_create-mfa-credentials", include_str!("../../../migrations/0009_create-mfa-credentials_up.sql")),
+    ("0010_add-lockout-to-users", include_str!("../../../migrations/0010_add-lockout-to-users_up.sql")),
+    ("0011_add-version-to-users", include_str!("../../../migrations/0011_add-version-to-users_up.sql")),
 ];
 
 impl PoolFactory {
@@ -19,10 +27,12 @@ impl PoolFactory {
     }
 
     pub async fn create(&mut self) -> Pool {
+        let tls = self.config_factory.create_tls().expect("could not set up postgres tls");
         let pool: Pool = self
             .config_factory
             .create()
-            .create_pool(Some(Runtime::Tokio1), NoTls)
+            .expect("invalid postgres settings")
+            .create_pool(Some(Runtime::Tokio1), tls)
             .expect("could not create postgres connection pool");
         self.migrate(&pool).await;
         pool
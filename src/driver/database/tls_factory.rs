@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, Error, RootCertStore, SignatureScheme};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::driver::error::DriverError;
+use crate::settings::SslMode;
+
+/// Builds the rustls connector of the pool. Server certificates are checked
+/// the way libpq does for the same `sslmode`.
+pub struct TlsFactory {
+    ssl_mode: SslMode,
+    root_cert: Option<PathBuf>,
+    client_identity: Option<(PathBuf, PathBuf)>,
+}
+
+impl TlsFactory {
+    /// `client_identity` is the PEM certificate chain and private key file.
+    pub fn new(ssl_mode: SslMode, root_cert: Option<PathBuf>, client_identity: Option<(PathBuf, PathBuf)>) -> Self {
+        Self {
+            ssl_mode,
+            root_cert,
+            client_identity,
+        }
+    }
+    pub fn create(&self) -> Result<MakeRustlsConnect, DriverError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier: Arc<dyn ServerCertVerifier> = match (self.ssl_mode, &self.root_cert) {
+            (SslMode::VerifyFull, _) => self.webpki_verifier(&provider)?,
+            // like libpq, a root certificate turns on verification for the weaker modes too
+            (SslMode::VerifyCa, _) | (_, Some(_)) => Arc::new(AnyServerName(self.webpki_verifier(&provider)?)),
+            (SslMode::Disable | SslMode::Prefer | SslMode::Require, None) => {
+                Arc::new(UnverifiedServer(provider.clone()))
+            }
+        };
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|err| DriverError::Config(format!("tls: {err}")))?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier);
+        let config = match &self.client_identity {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(read_certificates(cert)?, read_private_key(key)?)
+                .map_err(|err| DriverError::Config(format!("{}: {err}", key.display())))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(MakeRustlsConnect::new(config))
+    }
+    fn webpki_verifier(&self, provider: &Arc<CryptoProvider>) -> Result<Arc<WebPkiServerVerifier>, DriverError> {
+        let roots = match &self.root_cert {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in read_certificates(path)? {
+                    roots
+                        .add(certificate)
+                        .map_err(|err| DriverError::Config(format!("{}: {err}", path.display())))?;
+                }
+                roots
+            }
+            None => RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        };
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|err| DriverError::Config(format!("tls: {err}")))
+    }
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, DriverError> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| DriverError::Config(format!("{}: {err}", path.display())))?;
+    if certificates.is_empty() {
+        return Err(DriverError::Config(format!("{}: no certificates found", path.display())));
+    }
+    Ok(certificates)
+}
+
+fn read_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, DriverError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|err| DriverError::Config(format!("{}: {err}", path.display())))
+}
+
+/// `verify-ca`: the chain has to end in a trusted root, but the certificate
+/// may be issued for any host name.
+#[derive(Debug)]
+struct AnyServerName(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for AnyServerName {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        // the name is only checked once the chain is known to be valid
+        match self.0.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+            Err(Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
+/// `prefer` and `require` without a root certificate: the connection is
+/// encrypted, but any certificate is accepted for the server.
+#[derive(Debug)]
+struct UnverifiedServer(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for UnverifiedServer {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
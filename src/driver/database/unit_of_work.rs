@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use deadpool_postgres::{Object, Transaction};
+use log::warn;
+use tokio_postgres::IsolationLevel;
+
+use crate::driver::error::DriverError;
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// A serializable transaction shared by every DAO call of one aggregate
+/// write. Nothing is visible to others until `commit`, dropping it without
+/// committing rolls everything back.
+pub struct UnitOfWork<'a> {
+    transaction: Transaction<'a>,
+}
+
+impl<'a> UnitOfWork<'a> {
+    pub async fn begin(client: &'a mut Object) -> Result<Self, DriverError> {
+        let transaction = client
+            .build_transaction()
+            .isolation_level(IsolationLevel::Serializable)
+            .start()
+            .await?;
+        Ok(Self { transaction })
+    }
+    pub fn transaction(&self) -> &Transaction<'a> {
+        &self.transaction
+    }
+    pub async fn commit(self) -> Result<(), DriverError> {
+        self.transaction.commit().await.map_err(DriverError::from)
+    }
+}
+
+/// Decides whether a unit of work that Postgres aborted because of a
+/// serialization failure or deadlock runs again.
+#[derive(Debug, Default)]
+pub struct Retry {
+    attempt: u32,
+}
+
+impl Retry {
+    /// Backs off a little longer each time before answering `true`.
+    pub async fn should_retry<T>(&mut self, result: &Result<T, DriverError>) -> bool {
+        self.attempt += 1;
+        match result {
+            Err(DriverError::Retryable(message)) if self.attempt < MAX_ATTEMPTS => {
+                warn!("Retry.should_retry() after attempt {}: {message}", self.attempt);
+                actix_web::rt::time::sleep(RETRY_BACKOFF * self.attempt).await;
+                true
+            }
+            _ => false,
+        }
+    }
+}
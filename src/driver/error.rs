@@ -1,29 +1,89 @@
+use std::fmt::{Display, Formatter};
+
 use deadpool_postgres::PoolError;
+use tokio_postgres::error::SqlState;
 use tokio_postgres::Error;
+use uuid::Uuid;
+
+use crate::business::error::BusinessError;
 
 #[derive(Debug)]
-pub struct DriverError {
-    message: String,
+pub enum DriverError {
+    /// A unique constraint rejected the write, holds the constraint name.
+    UniqueViolation(String),
+    /// A foreign key constraint rejected the write, holds the constraint name.
+    ForeignKeyViolation(String),
+    /// Serialization failure or deadlock, the transaction can be retried as is.
+    Retryable(String),
+    /// An optimistic update matched no row, holds the id of the row.
+    StaleVersion(Uuid),
+    Database(String),
+    Io(String),
+    Crypto(&'static str),
+    /// The connection settings are incomplete or point at unusable files.
+    Config(String),
 }
 
-impl DriverError {
-    pub fn message(&self) -> &str {
-        &self.message
+impl Display for DriverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriverError::UniqueViolation(constraint) => write!(f, "unique violation: {constraint}"),
+            DriverError::ForeignKeyViolation(constraint) => write!(f, "foreign key violation: {constraint}"),
+            DriverError::Retryable(message) => write!(f, "retryable database error: {message}"),
+            DriverError::StaleVersion(id) => write!(f, "stale version of row {id}"),
+            DriverError::Database(message) => write!(f, "database error: {message}"),
+            DriverError::Io(message) => write!(f, "i/o error: {message}"),
+            DriverError::Crypto(message) => write!(f, "crypto error: {message}"),
+            DriverError::Config(message) => write!(f, "invalid configuration: {message}"),
+        }
     }
 }
 
 impl From<PoolError> for DriverError {
     fn from(error: PoolError) -> Self {
-        Self {
-            message: error.to_string(),
+        match error {
+            PoolError::Backend(error) => DriverError::from(error),
+            error => DriverError::Database(error.to_string()),
         }
     }
 }
 
+/// Classifies by SQLSTATE so callers can react without parsing messages.
 impl From<Error> for DriverError {
     fn from(error: Error) -> Self {
-        Self {
-            message: error.to_string(),
+        let constraint = || {
+            error
+                .as_db_error()
+                .and_then(|db_error| db_error.constraint())
+                .unwrap_or_default()
+                .to_owned()
+        };
+        match error.code() {
+            Some(code) if *code == SqlState::UNIQUE_VIOLATION => DriverError::UniqueViolation(constraint()),
+            Some(code) if *code == SqlState::FOREIGN_KEY_VIOLATION => {
+                DriverError::ForeignKeyViolation(constraint())
+            }
+            Some(code)
+                if *code == SqlState::T_R_SERIALIZATION_FAILURE
+                    || *code == SqlState::T_R_DEADLOCK_DETECTED =>
+            {
+                DriverError::Retryable(error.to_string())
+            }
+            _ => DriverError::Database(error.to_string()),
         }
     }
 }
+
+impl From<std::io::Error> for DriverError {
+    fn from(error: std::io::Error) -> Self {
+        DriverError::Io(error.to_string())
+    }
+}
+
+/// For the adapters of the other business ports, none of their failures is
+/// something the services can react to.
+impl From<DriverError> for BusinessError {
+    fn from(error: DriverError) -> Self {
+        BusinessError::Internal(error.to_string())
+    }
+}
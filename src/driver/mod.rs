@@ -1,3 +1,8 @@
+pub mod breached_passwords;
+pub mod crypto;
 pub mod dao;
 pub mod database;
 pub mod error;
+pub mod notifier;
+pub mod password;
+pub mod user_repository;
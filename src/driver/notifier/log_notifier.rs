@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use log::info;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+use crate::business::auth::notifier::Notifier;
+use crate::business::error::BusinessError;
+use crate::driver::error::DriverError;
+
+/// Development notifier that appends messages to a file, or logs them if no
+/// file is configured. Never use it in production, it exposes reset tokens.
+#[derive(Debug)]
+pub struct LogNotifier {
+    file: Option<PathBuf>,
+}
+
+impl LogNotifier {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Self { file }
+    }
+    async fn append(path: &PathBuf, message: &str) -> Result<(), DriverError> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path).await?;
+        file.write_all(message.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn send_password_reset(&self, username: &str, reset_token: &str) -> Result<(), BusinessError> {
+        let message = format!("password reset for {username}: {reset_token}\n");
+        match &self.file {
+            Some(path) => Self::append(path, &message).await?,
+            None => info!("LogNotifier {}", message.trim_end()),
+        }
+        Ok(())
+    }
+}
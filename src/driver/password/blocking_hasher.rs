@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+
+use crate::core::password::PasswordHasher;
+use crate::driver::password::scheme::PasswordScheme;
+
+/// Runs the password scheme on tokio's blocking pool. The semaphore bounds
+/// how many hashes are computed at once, so a burst of logins queues up
+/// instead of occupying every blocking thread.
+#[derive(Debug)]
+pub struct BlockingHasher {
+    scheme: Arc<PasswordScheme>,
+    permits: Arc<Semaphore>,
+}
+
+impl BlockingHasher {
+    pub fn new(scheme: PasswordScheme, max_concurrency: usize) -> Self {
+        Self {
+            scheme: Arc::new(scheme),
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+        }
+    }
+}
+
+#[async_trait]
+impl PasswordHasher for BlockingHasher {
+    async fn hash(&self, password: &str) -> String {
+        let _permit = self.permits.acquire().await.expect("semaphore is never closed");
+        let password = password.to_owned();
+        let scheme = self.scheme.clone();
+        tokio::task::spawn_blocking(move || scheme.hash(&password))
+            .await
+            .expect("hashing task panicked")
+    }
+    async fn verify(&self, password: &str, hash: &str) -> bool {
+        let _permit = self.permits.acquire().await.expect("semaphore is never closed");
+        let password = password.to_owned();
+        let hash = hash.to_owned();
+        tokio::task::spawn_blocking(move || PasswordScheme::verify(&password, &hash))
+            .await
+            .expect("hashing task panicked")
+    }
+    fn needs_rehash(&self, hash: &str) -> bool {
+        !self.scheme.is_current(hash)
+    }
+}
@@ -0,0 +1,2 @@
+pub mod blocking_hasher;
+pub mod scheme;
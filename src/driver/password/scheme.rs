@@ -0,0 +1,87 @@
+use argon2::password_hash::{PasswordHash, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher as _, PasswordVerifier, Version};
+use ring::rand::{SecureRandom, SystemRandom};
+
+const ARGON2_SALT_BYTES: usize = 16;
+const BCRYPT_COSTS: std::ops::RangeInclusive<u32> = 4..=31;
+
+/// Algorithm and parameters used for new password hashes. Stored hashes are
+/// verified with whatever algorithm their PHC string names, so existing users
+/// can still log in after the scheme changed.
+#[derive(Debug, Clone)]
+pub enum PasswordScheme {
+    Bcrypt { cost: u32 },
+    Argon2id(Params),
+}
+
+impl PasswordScheme {
+    pub fn bcrypt(cost: u32) -> Result<Self, String> {
+        if !BCRYPT_COSTS.contains(&cost) {
+            return Err(format!(
+                "bcrypt_cost must be between {} and {}",
+                BCRYPT_COSTS.start(),
+                BCRYPT_COSTS.end()
+            ));
+        }
+        Ok(PasswordScheme::Bcrypt { cost })
+    }
+    pub fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, String> {
+        Params::new(memory_kib, iterations, parallelism, None)
+            .map(PasswordScheme::Argon2id)
+            .map_err(|err| err.to_string())
+    }
+    pub fn hash(&self, password: &str) -> String {
+        match self {
+            PasswordScheme::Bcrypt { cost } => {
+                bcrypt::hash(password, *cost).expect("bcrypt cost is within the valid range")
+            }
+            PasswordScheme::Argon2id(params) => {
+                let mut salt = [0u8; ARGON2_SALT_BYTES];
+                SystemRandom::new()
+                    .fill(&mut salt)
+                    .expect("system random number generator failed");
+                let salt = SaltString::encode_b64(&salt).expect("salt length is valid");
+                argon2id(params.clone())
+                    .hash_password(password.as_bytes(), &salt)
+                    .expect("argon2 parameters are valid")
+                    .to_string()
+            }
+        }
+    }
+    pub fn verify(password: &str, hash: &str) -> bool {
+        if is_bcrypt(hash) {
+            return bcrypt::verify(password, hash).unwrap_or(false);
+        }
+        PasswordHash::new(hash).is_ok_and(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+    }
+    /// Whether the hash was produced by this scheme with exactly these parameters.
+    pub fn is_current(&self, hash: &str) -> bool {
+        match self {
+            PasswordScheme::Bcrypt { cost } => {
+                is_bcrypt(hash) && hash.split('$').nth(2) == Some(format!("{cost:02}").as_str())
+            }
+            PasswordScheme::Argon2id(params) => PasswordHash::new(hash).is_ok_and(|parsed| {
+                parsed.algorithm == Algorithm::Argon2id.ident()
+                    && Params::try_from(&parsed).is_ok_and(|stored| {
+                        stored.m_cost() == params.m_cost()
+                            && stored.t_cost() == params.t_cost()
+                            && stored.p_cost() == params.p_cost()
+                    })
+            }),
+        }
+    }
+}
+
+fn argon2id(params: Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
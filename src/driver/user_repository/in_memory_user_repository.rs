@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use log::debug;
+use uuid::Uuid;
+
+use crate::business::user::repository::{RepositoryError, UserRepository, UserRepositoryFactory};
+use crate::core::mfa::MfaDto;
+use crate::core::page::{Page, SortOrder};
+use crate::core::token::{Token, TokenDto};
+use crate::core::user::{User, UserDto};
+
+#[derive(Debug, Default)]
+struct Tables {
+    users: HashMap<Uuid, UserDto>,
+    tokens: HashMap<Uuid, TokenDto>,
+    mfa_credentials: HashMap<Uuid, MfaDto>,
+}
+
+impl Tables {
+    fn check_username(&self, user_dto: &UserDto) -> Result<(), RepositoryError> {
+        let is_taken = self
+            .users
+            .values()
+            .any(|other| other.username() == user_dto.username() && other.id() != user_dto.id());
+        if is_taken {
+            return Err(RepositoryError::UsernameTaken);
+        }
+        Ok(())
+    }
+    fn load(&self, user_dto: &UserDto) -> User {
+        let mut token_dtos = self
+            .tokens
+            .values()
+            .filter(|token_dto| token_dto.user_id() == user_dto.id())
+            .cloned()
+            .collect::<Vec<_>>();
+        token_dtos.sort_by_key(|token_dto| (*token_dto.created_at(), *token_dto.id()));
+        User::from_dto(user_dto, &token_dtos, self.mfa_credentials.get(user_dto.id()))
+    }
+}
+
+/// Keeps users in process memory with the same semantics as the Postgres
+/// tables, including the unique username. Meant for tests and local
+/// development, everything is gone when the process exits. Clones share the
+/// same tables, each opened repository is one.
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryUserRepository {
+    tables: Arc<RwLock<Tables>>,
+}
+
+#[async_trait]
+impl UserRepositoryFactory for InMemoryUserRepository {
+    async fn open(&self) -> Result<Box<dyn UserRepository>, RepositoryError> {
+        debug!("InMemoryUserRepository.open()");
+        Ok(Box::new(self.clone()))
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn create(&self, user: &User) -> Result<(), RepositoryError> {
+        debug!("InMemoryUserRepository.create() with inputs: user={:?}", user);
+        let user_dto = user.to_dto();
+        let mut tables = self.tables.write().unwrap();
+        if tables.users.contains_key(user_dto.id()) {
+            return Err(RepositoryError::DuplicateId);
+        }
+        tables.check_username(&user_dto)?;
+        for token_dto in user_dto.tokens() {
+            tables.tokens.insert(*token_dto.id(), token_dto.clone());
+        }
+        if let Some(mfa_dto) = user_dto.mfa() {
+            tables.mfa_credentials.insert(*user_dto.id(), mfa_dto.clone());
+        }
+        tables.users.insert(*user_dto.id(), user_dto);
+        Ok(())
+    }
+    async fn update(&self, user: &User) -> Result<(), RepositoryError> {
+        debug!("InMemoryUserRepository.update() with inputs: user={:?}", user);
+        let user_dto = user.to_dto();
+        let mut tables = self.tables.write().unwrap();
+        let is_current = tables
+            .users
+            .get(user_dto.id())
+            .is_some_and(|stored| stored.version() == user_dto.version());
+        if !is_current {
+            return Err(RepositoryError::Stale);
+        }
+        tables.check_username(&user_dto)?;
+        for token_dto in user_dto.tokens() {
+            tables.tokens.insert(*token_dto.id(), token_dto.clone());
+        }
+        if let Some(mfa_dto) = user_dto.mfa() {
+            tables.mfa_credentials.insert(*user_dto.id(), mfa_dto.clone());
+        }
+        tables.users.insert(*user_dto.id(), user_dto.with_next_version());
+        Ok(())
+    }
+    async fn delete_by_id(&self, user_id: &Uuid) -> Result<(), RepositoryError> {
+        debug!("InMemoryUserRepository.delete_by_id() with inputs: user_id={:?}", user_id);
+        let mut tables = self.tables.write().unwrap();
+        tables.users.remove(user_id);
+        tables.tokens.retain(|_, token_dto| token_dto.user_id() != user_id);
+        tables.mfa_credentials.remove(user_id);
+        Ok(())
+    }
+    async fn find_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError> {
+        debug!("InMemoryUserRepository.find_by_id() with inputs: user_id={:?}", user_id);
+        let tables = self.tables.read().unwrap();
+        Ok(tables.users.get(user_id).map(|user_dto| tables.load(user_dto)))
+    }
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError> {
+        debug!("InMemoryUserRepository.find_by_username() with inputs: username={:?}", username);
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .users
+            .values()
+            .find(|user_dto| user_dto.username() == username)
+            .map(|user_dto| tables.load(user_dto)))
+    }
+    async fn find_by_token(&self, key: &str) -> Result<Option<User>, RepositoryError> {
+        debug!("InMemoryUserRepository.find_by_token() with inputs: key={:?}", key);
+        let key_hash = Token::hash_key(key);
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .tokens
+            .values()
+            .find(|token_dto| token_dto.key_hash() == key_hash)
+            .and_then(|token_dto| tables.users.get(token_dto.user_id()))
+            .map(|user_dto| tables.load(user_dto)))
+    }
+    async fn find_page(
+        &self,
+        username_prefix: Option<&str>,
+        cursor: Option<&Uuid>,
+        order: SortOrder,
+        limit: u32,
+    ) -> Result<Page<User>, RepositoryError> {
+        debug!(
+            "InMemoryUserRepository.find_page() with inputs: username_prefix={:?}, cursor={:?}, order={:?}, limit={:?}",
+            username_prefix, cursor, order, limit
+        );
+        let tables = self.tables.read().unwrap();
+        let mut user_dtos = tables
+            .users
+            .values()
+            .filter(|user_dto| username_prefix.is_none_or(|prefix| user_dto.username().starts_with(prefix)))
+            .filter(|user_dto| match (cursor, order) {
+                (None, _) => true,
+                (Some(cursor), SortOrder::Asc) => user_dto.id() > cursor,
+                (Some(cursor), SortOrder::Desc) => user_dto.id() < cursor,
+            })
+            .collect::<Vec<_>>();
+        user_dtos.sort_by_key(|user_dto| *user_dto.id());
+        if order == SortOrder::Desc {
+            user_dtos.reverse();
+        }
+        let has_more = user_dtos.len() > limit as usize;
+        user_dtos.truncate(limit as usize);
+        let next_cursor = user_dtos.last().filter(|_| has_more).map(|user_dto| *user_dto.id());
+        let users = user_dtos.into_iter().map(|user_dto| tables.load(user_dto)).collect();
+        Ok(Page::new(users, next_cursor))
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use deadpool_postgres::Object;
+use log::debug;
+use uuid::Uuid;
+
+use tokio::sync::Mutex;
+
+use crate::business::user::repository::{RepositoryError, UserRepository, UserRepositoryFactory};
+use crate::core::mfa::MfaDto;
+use crate::core::page::{Page, SortOrder};
+use crate::core::token::{Token, TokenDto};
+use crate::core::user::{User, UserDto};
+use crate::driver::dao::token::TokenDao;
+use crate::driver::dao::user::UserDao;
+use crate::driver::database::client_adapter::CachingClient;
+use crate::driver::database::pool_adapter::PoolAdapter;
+use crate::driver::database::unit_of_work::{Retry, UnitOfWork};
+use crate::driver::error::DriverError;
+
+const USERNAME_CONSTRAINT: &str = "users_username_key";
+const ID_CONSTRAINT: &str = "users_pkey";
+
+/// Opens a `PostgresUserRepository` on a connection checked out of the pool.
+#[derive(Debug)]
+pub struct PostgresUserRepositoryFactory {
+    pool: Arc<PoolAdapter>,
+    user_dao: Arc<UserDao>,
+    token_dao: Arc<TokenDao>,
+}
+
+impl PostgresUserRepositoryFactory {
+    pub fn new(pool: Arc<PoolAdapter>, user_dao: UserDao, token_dao: TokenDao) -> Self {
+        Self {
+            pool,
+            user_dao: Arc::new(user_dao),
+            token_dao: Arc::new(token_dao),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepositoryFactory for PostgresUserRepositoryFactory {
+    async fn open(&self) -> Result<Box<dyn UserRepository>, RepositoryError> {
+        debug!("PostgresUserRepositoryFactory.open()");
+        Ok(Box::new(PostgresUserRepository {
+            client: Mutex::new(self.pool.get_connection().await?),
+            user_dao: self.user_dao.clone(),
+            token_dao: self.token_dao.clone(),
+        }))
+    }
+}
+
+/// Runs every operation on the one pooled connection it was opened with, so a
+/// request that loads and then updates a user holds a single connection from
+/// start to end. The connection goes back to the pool when the repository is
+/// dropped. Writes span the whole aggregate in one unit of work, an update only
+/// applies if the version of the user row is still the loaded one.
+#[derive(Debug)]
+pub struct PostgresUserRepository {
+    client: Mutex<Object>,
+    user_dao: Arc<UserDao>,
+    token_dao: Arc<TokenDao>,
+}
+
+impl PostgresUserRepository {
+    async fn save(&self, user_dto: &UserDto, is_new: bool) -> Result<(), DriverError> {
+        let mut client = self.client.lock().await;
+        let mut retry = Retry::default();
+        loop {
+            let result = self.try_save(&mut client, user_dto, is_new).await;
+            if !retry.should_retry(&result).await {
+                return result;
+            }
+        }
+    }
+    /// Writes the user with its tokens and MFA credentials, all or nothing.
+    async fn try_save(
+        &self,
+        client: &mut Object,
+        user_dto: &UserDto,
+        is_new: bool,
+    ) -> Result<(), DriverError> {
+        let unit_of_work = UnitOfWork::begin(client).await?;
+        let transaction = unit_of_work.transaction();
+        if is_new {
+            self.user_dao.create(transaction, user_dto).await?;
+        } else {
+            self.user_dao.update(transaction, user_dto).await?;
+        }
+        for token_dto in user_dto.tokens() {
+            if is_new {
+                self.token_dao.create(transaction, token_dto).await?;
+            } else {
+                self.token_dao.save(transaction, token_dto).await?;
+            }
+        }
+        if let Some(mfa_dto) = user_dto.mfa() {
+            self.user_dao.save_mfa(transaction, user_dto.id(), mfa_dto).await?;
+        }
+        unit_of_work.commit().await
+    }
+    async fn try_delete_by_id(&self, client: &mut Object, user_id: &Uuid) -> Result<(), DriverError> {
+        let unit_of_work = UnitOfWork::begin(client).await?;
+        self.token_dao.delete_by_user_id(unit_of_work.transaction(), user_id).await?;
+        self.user_dao.delete_by_id(unit_of_work.transaction(), user_id).await?;
+        unit_of_work.commit().await
+    }
+    /// Builds the aggregates with one query for all tokens and one for all
+    /// MFA credentials, however many users there are.
+    async fn load(
+        &self,
+        client: &impl CachingClient,
+        user_dtos: Vec<UserDto>,
+    ) -> Result<Vec<User>, DriverError> {
+        if user_dtos.is_empty() {
+            return Ok(Vec::new());
+        }
+        let user_ids = user_dtos.iter().map(|user_dto| *user_dto.id()).collect::<Vec<_>>();
+        let mut token_dtos_by_user_id = HashMap::<Uuid, Vec<TokenDto>>::new();
+        for token_dto in self.token_dao.find_by_user_ids(client, &user_ids).await? {
+            token_dtos_by_user_id.entry(*token_dto.user_id()).or_default().push(token_dto);
+        }
+        let mfa_dto_by_user_id = self
+            .user_dao
+            .find_mfa_by_user_ids(client, &user_ids)
+            .await?
+            .into_iter()
+            .collect::<HashMap<Uuid, MfaDto>>();
+        Ok(user_dtos
+            .iter()
+            .map(|user_dto| {
+                User::from_dto(
+                    user_dto,
+                    token_dtos_by_user_id.get(user_dto.id()).map_or(&[], Vec::as_slice),
+                    mfa_dto_by_user_id.get(user_dto.id()),
+                )
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresUserRepository {
+    async fn create(&self, user: &User) -> Result<(), RepositoryError> {
+        debug!("PostgresUserRepository.create() with inputs: user={:?}", user);
+        Ok(self.save(&user.to_dto(), true).await?)
+    }
+    async fn update(&self, user: &User) -> Result<(), RepositoryError> {
+        debug!("PostgresUserRepository.update() with inputs: user={:?}", user);
+        Ok(self.save(&user.to_dto(), false).await?)
+    }
+    async fn delete_by_id(&self, user_id: &Uuid) -> Result<(), RepositoryError> {
+        debug!("PostgresUserRepository.delete_by_id() with inputs: user_id={:?}", user_id);
+        let mut client = self.client.lock().await;
+        let mut retry = Retry::default();
+        loop {
+            let result = self.try_delete_by_id(&mut client, user_id).await;
+            if !retry.should_retry(&result).await {
+                return Ok(result?);
+            }
+        }
+    }
+    async fn find_by_id(&self, user_id: &Uuid) -> Result<Option<User>, RepositoryError> {
+        debug!("PostgresUserRepository.find_by_id() with inputs: user_id={:?}", user_id);
+        let client = self.client.lock().await;
+        let user_dto = self.user_dao.find_by_id(&*client, user_id).await?;
+        Ok(self.load(&*client, user_dto.into_iter().collect()).await?.pop())
+    }
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, RepositoryError> {
+        debug!("PostgresUserRepository.find_by_username() with inputs: username={:?}", username);
+        let client = self.client.lock().await;
+        let user_dto = self.user_dao.find_by_username(&*client, username).await?;
+        Ok(self.load(&*client, user_dto.into_iter().collect()).await?.pop())
+    }
+    async fn find_by_token(&self, key: &str) -> Result<Option<User>, RepositoryError> {
+        debug!("PostgresUserRepository.find_by_token() with inputs: key={:?}", key);
+        let client = self.client.lock().await;
+        let user_dto = self.user_dao.find_by_token(&*client, &Token::hash_key(key)).await?;
+        Ok(self.load(&*client, user_dto.into_iter().collect()).await?.pop())
+    }
+    async fn find_page(
+        &self,
+        username_prefix: Option<&str>,
+        cursor: Option<&Uuid>,
+        order: SortOrder,
+        limit: u32,
+    ) -> Result<Page<User>, RepositoryError> {
+        debug!(
+            "PostgresUserRepository.find_page() with inputs: username_prefix={:?}, cursor={:?}, order={:?}, limit={:?}",
+            username_prefix, cursor, order, limit
+        );
+        let client = self.client.lock().await;
+        // one extra row tells whether another page follows
+        let mut user_dtos = self
+            .user_dao
+            .find_page(&*client, username_prefix, cursor, order, i64::from(limit) + 1)
+            .await?;
+        let has_more = user_dtos.len() > limit as usize;
+        user_dtos.truncate(limit as usize);
+        let next_cursor = user_dtos.last().filter(|_| has_more).map(|user_dto| *user_dto.id());
+        let result = Ok(Page::new(self.load(&*client, user_dtos).await?, next_cursor));
+        debug!("PostgresUserRepository.find_page() with output: {:?}", result);
+        result
+    }
+}
+
+/// Only this repository knows the constraint names of the Users table.
+impl From<DriverError> for RepositoryError {
+    fn from(error: DriverError) -> Self {
+        match error {
+            DriverError::UniqueViolation(constraint) if constraint == USERNAME_CONSTRAINT => {
+                RepositoryError::UsernameTaken
+            }
+            DriverError::UniqueViolation(constraint) if constraint == ID_CONSTRAINT => RepositoryError::DuplicateId,
+            DriverError::Retryable(message) => RepositoryError::Retryable(message),
+            DriverError::StaleVersion(_) => RepositoryError::Stale,
+            error => RepositoryError::Other(error.to_string()),
+        }
+    }
+}
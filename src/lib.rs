@@ -0,0 +1,6 @@
+pub mod api;
+pub mod bootstrap;
+pub mod business;
+pub mod core;
+pub mod driver;
+pub mod settings;
@@ -1,55 +1,25 @@
-use std::sync::Arc;
-
 use actix_web::{App, HttpServer};
 use actix_web::middleware::Logger;
-use actix_web::web::{Data, get, post, scope};
-
-use crate::api::auth::handler as auth_handler;
-use crate::api::user::handler as user_handler;
-use crate::business::auth::service::AuthService;
-use crate::business::user::repository::UserRepository;
-use crate::business::user::service::UserService;
-use crate::driver::dao::token::TokenDao;
-use crate::driver::dao::user::UserDao;
-use crate::driver::database::config_factory::ConfigFactory;
-use crate::driver::database::pool_adapter::PoolAdapter;
-use crate::driver::database::pool_factory::PoolFactory;
+use clap::Parser;
 
-mod api;
-mod business;
-mod core;
-mod driver;
+use abcd_layered_architecture::bootstrap;
+use abcd_layered_architecture::settings::Cli;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init();
 
-    let address = std::env::var("ADDRESS").unwrap_or_else(|_| "127.0.0.1:8000".into());
-
-    let mut pool_factory = PoolFactory::new(ConfigFactory);
-    let pool = pool_factory.create().await;
-    let pool_adapter = Arc::new(PoolAdapter::new(pool));
-    let user_dao = UserDao::new(pool_adapter.clone());
-    let token_dao = TokenDao::new(pool_adapter.clone());
-    let user_repository = Arc::new(UserRepository::new(user_dao, token_dao));
-    let user_service = Arc::new(UserService::new(user_repository.clone()));
-    let auth_service = Arc::new(AuthService::new(user_repository.clone()));
+    let settings = bootstrap::load_settings(&Cli::parse(), |name| std::env::var(name).ok()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    let address = settings.server.address.clone();
+    let app_builder = bootstrap::from_settings(settings).await;
 
     HttpServer::new(move || {
         App::new()
-            .app_data(Data::from(user_service.clone()))
-            .app_data(Data::from(auth_service.clone()))
             .wrap(Logger::default())
-            .service(scope("")
-                .route("/login", post().to(auth_handler::login))
-                .route("/refresh", get().to(auth_handler::refresh))
-                .route("/logout", post().to(auth_handler::logout))
-                .service(scope("/users")
-                    .route("", get().to(user_handler::index))
-                    .route("/protected", get().to(user_handler::protected_index))
-                    .route("/{id}", get().to(user_handler::show))
-                    .route("/register", post().to(user_handler::register))
-                    .route("/delete", post().to(user_handler::delete))))
+            .configure(|cfg| app_builder.configure(cfg))
     })
     .bind(&address)?
     .run()
@@ -0,0 +1,695 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::Engine;
+use clap::Parser;
+use jsonwebtoken::Algorithm;
+use serde::Deserialize;
+
+use crate::core::lockout::LockoutPolicy;
+use crate::core::token::TokenLifetimes;
+
+const DEFAULT_SETTINGS_FILE: &str = "config.toml";
+const MFA_ENCRYPTION_KEY_BYTES: usize = 32;
+
+/// Command line flags of the server. Every setting but the secrets can also be
+/// overridden with `--set` and the name of its environment variable.
+#[derive(Parser, Debug, Default)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML settings file, `config.toml` is read when present
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. 0.0.0.0:8000
+    #[arg(long)]
+    pub address: Option<String>,
+    /// Path all routes are mounted under, e.g. /auth
+    #[arg(long)]
+    pub route_prefix: Option<String>,
+    /// Overrides a setting by its environment variable name, e.g. JWT_TTL_SECS=300
+    #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_assignment)]
+    pub overrides: Vec<(String, String)>,
+}
+
+fn parse_assignment(argument: &str) -> Result<(String, String), String> {
+    argument
+        .split_once('=')
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got {argument:?}"))
+}
+
+/// Lists every problem found while loading, so a broken deployment can be
+/// fixed in one go.
+#[derive(Debug)]
+pub struct SettingsError {
+    messages: Vec<String>,
+}
+
+impl SettingsError {
+    pub fn new(messages: Vec<String>) -> Self {
+        Self { messages }
+    }
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid settings:")?;
+        self.messages.iter().try_for_each(|message| write!(f, "\n  - {message}"))
+    }
+}
+
+/// A value that must not end up in logs; `Debug` prints a placeholder.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryBackend {
+    Postgres,
+    Memory,
+}
+
+impl FromStr for RepositoryBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "postgres" => Ok(RepositoryBackend::Postgres),
+            "memory" => Ok(RepositoryBackend::Memory),
+            _ => Err(format!("unknown user repository: {value}")),
+        }
+    }
+}
+
+/// There is no default, a deployment has to pick how reset tokens reach users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierBackend {
+    /// Writes reset tokens in plain text, for development only.
+    Log,
+}
+
+impl FromStr for NotifierBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(NotifierBackend::Log),
+            _ => Err(format!("unknown notifier: {value}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+impl FromStr for PasswordAlgorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "argon2id" => Ok(PasswordAlgorithm::Argon2id),
+            "bcrypt" => Ok(PasswordAlgorithm::Bcrypt),
+            _ => Err(format!("unknown password hash algorithm: {value}")),
+        }
+    }
+}
+
+/// The libpq `sslmode` values apart from `allow`. Like libpq, `prefer` and
+/// `require` encrypt without checking the server certificate unless a root
+/// certificate is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(format!("unknown ssl mode: {value}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub tokens: TokenSettings,
+    pub jwt: JwtSettings,
+    pub password: PasswordSettings,
+    pub lockout: LockoutSettings,
+    pub mfa: MfaSettings,
+    pub notifier: NotifierSettings,
+    pub admin: AdminSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub address: String,
+    pub route_prefix: String,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8000".to_owned(),
+            route_prefix: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub backend: RepositoryBackend,
+    /// `postgres://` connection string. User, password, database name and
+    /// the ssl fields below take precedence over its parts, host and port
+    /// cannot be combined with it. Understands the libpq `sslmode`,
+    /// `sslrootcert`, `sslcert` and `sslkey` parameters.
+    pub url: Option<Secret>,
+    pub url_file: Option<PathBuf>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub dbname: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<Secret>,
+    pub password_file: Option<PathBuf>,
+    /// Defaults to the mode of the url, or `prefer`.
+    pub ssl_mode: Option<SslMode>,
+    /// PEM bundle the server certificate is checked against instead of the
+    /// Mozilla root certificates.
+    pub ssl_root_cert: Option<PathBuf>,
+    /// PEM client certificate chain and private key, for servers that
+    /// authenticate clients by certificate.
+    pub ssl_cert: Option<PathBuf>,
+    pub ssl_key: Option<PathBuf>,
+    pub connect_timeout_secs: Option<u64>,
+    pub pool: PoolSettings,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            backend: RepositoryBackend::Postgres,
+            url: None,
+            url_file: None,
+            host: None,
+            port: None,
+            dbname: None,
+            user: None,
+            password: None,
+            password_file: None,
+            ssl_mode: None,
+            ssl_root_cert: None,
+            ssl_cert: None,
+            ssl_key: None,
+            connect_timeout_secs: None,
+            pool: PoolSettings::default(),
+        }
+    }
+}
+
+/// Unset values keep the deadpool defaults: two connections per CPU and no
+/// timeouts.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolSettings {
+    pub max_size: Option<usize>,
+    /// How long to wait for a free connection when the pool is exhausted.
+    pub wait_timeout_secs: Option<u64>,
+    pub create_timeout_secs: Option<u64>,
+    pub recycle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenSettings {
+    pub pepper: Option<Secret>,
+    pub pepper_file: Option<PathBuf>,
+    pub refresh_ttl_secs: u64,
+    pub password_reset_ttl_secs: u64,
+    pub mfa_challenge_ttl_secs: u64,
+    pub refresh_cookie_name: String,
+}
+
+impl Default for TokenSettings {
+    fn default() -> Self {
+        Self {
+            pepper: None,
+            pepper_file: None,
+            refresh_ttl_secs: 60 * 60,
+            password_reset_ttl_secs: 15 * 60,
+            mfa_challenge_ttl_secs: 5 * 60,
+            refresh_cookie_name: "refresh-token".to_owned(),
+        }
+    }
+}
+
+impl TokenSettings {
+    pub fn lifetimes(&self) -> TokenLifetimes {
+        TokenLifetimes::new(
+            Duration::from_secs(self.refresh_ttl_secs),
+            Duration::from_secs(self.password_reset_ttl_secs),
+            Duration::from_secs(self.mfa_challenge_ttl_secs),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtSettings {
+    pub issuer: String,
+    pub ttl_secs: u64,
+    pub key_directory: PathBuf,
+    pub key_algorithm: Algorithm,
+    pub key_rotation_interval_secs: Option<u64>,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            issuer: "asdf".to_owned(),
+            ttl_secs: 15 * 60,
+            key_directory: PathBuf::from("keys"),
+            key_algorithm: Algorithm::ES256,
+            key_rotation_interval_secs: None,
+        }
+    }
+}
+
+impl JwtSettings {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordSettings {
+    pub algorithm: PasswordAlgorithm,
+    pub bcrypt_cost: u32,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    /// Defaults to the number of CPUs.
+    pub hashing_concurrency: Option<usize>,
+    pub min_length: usize,
+    pub breached_passwords_directory: Option<PathBuf>,
+}
+
+impl Default for PasswordSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: PasswordAlgorithm::Argon2id,
+            bcrypt_cost: 12,
+            argon2_memory_kib: 19 * 1024,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            hashing_concurrency: None,
+            min_length: 8,
+            breached_passwords_directory: None,
+        }
+    }
+}
+
+impl PasswordSettings {
+    pub fn hashing_concurrency(&self) -> usize {
+        self.hashing_concurrency
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |count| count.get()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutSettings {
+    pub max_attempts_per_user: u32,
+    pub max_attempts_per_ip: u32,
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+}
+
+impl Default for LockoutSettings {
+    fn default() -> Self {
+        Self {
+            max_attempts_per_user: 5,
+            max_attempts_per_ip: 20,
+            base_delay_secs: 30,
+            max_delay_secs: 60 * 60,
+        }
+    }
+}
+
+impl LockoutSettings {
+    pub fn account_policy(&self) -> LockoutPolicy {
+        self.policy(self.max_attempts_per_user)
+    }
+    pub fn client_policy(&self) -> LockoutPolicy {
+        self.policy(self.max_attempts_per_ip)
+    }
+    fn policy(&self, max_attempts: u32) -> LockoutPolicy {
+        LockoutPolicy::new(
+            max_attempts,
+            Duration::from_secs(self.base_delay_secs),
+            Duration::from_secs(self.max_delay_secs),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MfaSettings {
+    /// Base64 encoded AES-256 key the TOTP secrets are stored with.
+    pub encryption_key: Option<Secret>,
+    pub encryption_key_file: Option<PathBuf>,
+}
+
+impl MfaSettings {
+    pub fn encryption_key(&self) -> Result<Vec<u8>, String> {
+        let encoded = self.encryption_key.as_ref().ok_or("mfa.encryption_key must be set")?;
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(encoded.expose())
+            .map_err(|err| format!("mfa.encryption_key is not valid base64: {err}"))?;
+        if key.len() != MFA_ENCRYPTION_KEY_BYTES {
+            return Err(format!("mfa.encryption_key must decode to {MFA_ENCRYPTION_KEY_BYTES} bytes"));
+        }
+        Ok(key)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifierSettings {
+    pub backend: Option<NotifierBackend>,
+    /// Password reset messages of the log backend are appended here instead
+    /// of the log.
+    pub file: Option<PathBuf>,
+}
+
+/// Creates or promotes this user to admin at startup when both are set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSettings {
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub password_file: Option<PathBuf>,
+}
+
+type Setter = fn(&mut Settings, &str) -> Result<(), String>;
+
+/// Environment variables, also accepted by `--set`, and the setting each of
+/// them overrides. Setting a secret clears its file and vice versa, so the
+/// later layer wins either way.
+const OVERRIDES: &[(&str, Setter)] = &[
+    ("ADDRESS", |s, v| parse(v).map(|v| s.server.address = v)),
+    ("ROUTE_PREFIX", |s, v| parse(v).map(|v| s.server.route_prefix = v)),
+    ("USER_REPOSITORY", |s, v| parse(v).map(|v| s.database.backend = v)),
+    ("DATABASE_URL", |s, v| {
+        (s.database.url, s.database.url_file) = (Some(Secret::new(v)), None);
+        Ok(())
+    }),
+    ("DATABASE_URL_FILE", |s, v| {
+        (s.database.url, s.database.url_file) = (None, Some(PathBuf::from(v)));
+        Ok(())
+    }),
+    ("PG_HOST", |s, v| parse(v).map(|v| s.database.host = Some(v))),
+    ("PG_PORT", |s, v| parse(v).map(|v| s.database.port = Some(v))),
+    ("PG_DBNAME", |s, v| parse(v).map(|v| s.database.dbname = Some(v))),
+    ("PG_USER", |s, v| parse(v).map(|v| s.database.user = Some(v))),
+    ("PG_PASSWORD", |s, v| {
+        (s.database.password, s.database.password_file) = (Some(Secret::new(v)), None);
+        Ok(())
+    }),
+    ("PG_PASSWORD_FILE", |s, v| {
+        (s.database.password, s.database.password_file) = (None, Some(PathBuf::from(v)));
+        Ok(())
+    }),
+    ("PG_SSLMODE", |s, v| parse(v).map(|v| s.database.ssl_mode = Some(v))),
+    ("PG_SSLROOTCERT", |s, v| parse(v).map(|v| s.database.ssl_root_cert = Some(v))),
+    ("PG_SSLCERT", |s, v| parse(v).map(|v| s.database.ssl_cert = Some(v))),
+    ("PG_SSLKEY", |s, v| parse(v).map(|v| s.database.ssl_key = Some(v))),
+    ("PG_CONNECT_TIMEOUT_SECS", |s, v| parse(v).map(|v| s.database.connect_timeout_secs = Some(v))),
+    ("PG_POOL_MAX_SIZE", |s, v| parse(v).map(|v| s.database.pool.max_size = Some(v))),
+    ("PG_POOL_WAIT_TIMEOUT_SECS", |s, v| parse(v).map(|v| s.database.pool.wait_timeout_secs = Some(v))),
+    ("PG_POOL_CREATE_TIMEOUT_SECS", |s, v| parse(v).map(|v| s.database.pool.create_timeout_secs = Some(v))),
+    ("PG_POOL_RECYCLE_TIMEOUT_SECS", |s, v| parse(v).map(|v| s.database.pool.recycle_timeout_secs = Some(v))),
+    ("TOKEN_PEPPER", |s, v| {
+        (s.tokens.pepper, s.tokens.pepper_file) = (Some(Secret::new(v)), None);
+        Ok(())
+    }),
+    ("TOKEN_PEPPER_FILE", |s, v| {
+        (s.tokens.pepper, s.tokens.pepper_file) = (None, Some(PathBuf::from(v)));
+        Ok(())
+    }),
+    ("REFRESH_TOKEN_TTL_SECS", |s, v| parse(v).map(|v| s.tokens.refresh_ttl_secs = v)),
+    ("PASSWORD_RESET_TOKEN_TTL_SECS", |s, v| parse(v).map(|v| s.tokens.password_reset_ttl_secs = v)),
+    ("MFA_CHALLENGE_TOKEN_TTL_SECS", |s, v| parse(v).map(|v| s.tokens.mfa_challenge_ttl_secs = v)),
+    ("RT_COOKIE_NAME", |s, v| parse(v).map(|v| s.tokens.refresh_cookie_name = v)),
+    ("JWT_ISSUER", |s, v| parse(v).map(|v| s.jwt.issuer = v)),
+    ("JWT_TTL_SECS", |s, v| parse(v).map(|v| s.jwt.ttl_secs = v)),
+    ("JWT_KEY_DIR", |s, v| parse(v).map(|v| s.jwt.key_directory = v)),
+    ("JWT_KEY_ALGORITHM", |s, v| parse(v).map(|v| s.jwt.key_algorithm = v)),
+    ("JWT_KEY_ROTATION_INTERVAL_SECS", |s, v| parse(v).map(|v| s.jwt.key_rotation_interval_secs = Some(v))),
+    ("PASSWORD_HASH_ALGORITHM", |s, v| parse(v).map(|v| s.password.algorithm = v)),
+    ("BCRYPT_COST", |s, v| parse(v).map(|v| s.password.bcrypt_cost = v)),
+    ("ARGON2_MEMORY_KIB", |s, v| parse(v).map(|v| s.password.argon2_memory_kib = v)),
+    ("ARGON2_ITERATIONS", |s, v| parse(v).map(|v| s.password.argon2_iterations = v)),
+    ("ARGON2_PARALLELISM", |s, v| parse(v).map(|v| s.password.argon2_parallelism = v)),
+    ("PASSWORD_HASHING_CONCURRENCY", |s, v| parse(v).map(|v| s.password.hashing_concurrency = Some(v))),
+    ("PASSWORD_MIN_LENGTH", |s, v| parse(v).map(|v| s.password.min_length = v)),
+    ("BREACHED_PASSWORDS_DIR", |s, v| parse(v).map(|v| s.password.breached_passwords_directory = Some(v))),
+    ("LOGIN_MAX_ATTEMPTS_PER_USER", |s, v| parse(v).map(|v| s.lockout.max_attempts_per_user = v)),
+    ("LOGIN_MAX_ATTEMPTS_PER_IP", |s, v| parse(v).map(|v| s.lockout.max_attempts_per_ip = v)),
+    ("LOGIN_LOCKOUT_BASE_SECS", |s, v| parse(v).map(|v| s.lockout.base_delay_secs = v)),
+    ("LOGIN_LOCKOUT_MAX_SECS", |s, v| parse(v).map(|v| s.lockout.max_delay_secs = v)),
+    ("MFA_ENCRYPTION_KEY", |s, v| {
+        (s.mfa.encryption_key, s.mfa.encryption_key_file) = (Some(Secret::new(v)), None);
+        Ok(())
+    }),
+    ("MFA_ENCRYPTION_KEY_FILE", |s, v| {
+        (s.mfa.encryption_key, s.mfa.encryption_key_file) = (None, Some(PathBuf::from(v)));
+        Ok(())
+    }),
+    ("NOTIFIER_BACKEND", |s, v| parse(v).map(|v| s.notifier.backend = Some(v))),
+    ("NOTIFIER_FILE", |s, v| parse(v).map(|v| s.notifier.file = Some(v))),
+    ("ADMIN_USERNAME", |s, v| parse(v).map(|v| s.admin.username = Some(v))),
+    ("ADMIN_PASSWORD", |s, v| {
+        (s.admin.password, s.admin.password_file) = (Some(Secret::new(v)), None);
+        Ok(())
+    }),
+    ("ADMIN_PASSWORD_FILE", |s, v| {
+        (s.admin.password, s.admin.password_file) = (None, Some(PathBuf::from(v)));
+        Ok(())
+    }),
+];
+
+/// Command line arguments show up in the process list, so these can only be
+/// given through the environment, the settings file or their `_FILE` variant.
+const COMMAND_LINE_SECRETS: &[&str] = &[
+    "DATABASE_URL",
+    "PG_PASSWORD",
+    "TOKEN_PEPPER",
+    "MFA_ENCRYPTION_KEY",
+    "ADMIN_PASSWORD",
+];
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value.parse().map_err(|err: T::Err| err.to_string())
+}
+
+impl Settings {
+    /// Applies, from lowest to highest precedence: defaults, the TOML file,
+    /// environment variables and command line flags. Secrets given as files
+    /// are read afterwards.
+    pub fn read(cli: &Cli, env: impl Fn(&str) -> Option<String>) -> Result<Self, SettingsError> {
+        let mut settings = match cli.config.clone().or_else(|| env("CONFIG_FILE").map(PathBuf::from)) {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_SETTINGS_FILE).exists() => Self::from_file(Path::new(DEFAULT_SETTINGS_FILE))?,
+            None => Self::default(),
+        };
+
+        let mut messages = Vec::new();
+        for (name, setter) in OVERRIDES {
+            if let Some(value) = env(name) {
+                setter(&mut settings, &value).unwrap_or_else(|err| messages.push(format!("{name}: {err}")));
+            }
+        }
+        for (name, value) in &cli.overrides {
+            if COMMAND_LINE_SECRETS.contains(&name.as_str()) {
+                messages.push(format!("--set {name}: secrets are not accepted on the command line, use {name}_FILE"));
+                continue;
+            }
+            match OVERRIDES.iter().find(|(candidate, _)| candidate == name) {
+                Some((_, setter)) => setter(&mut settings, value)
+                    .unwrap_or_else(|err| messages.push(format!("--set {name}: {err}"))),
+                None => messages.push(format!("--set {name}: unknown setting")),
+            }
+        }
+        if let Some(address) = &cli.address {
+            settings.server.address = address.to_owned();
+        }
+        if let Some(route_prefix) = &cli.route_prefix {
+            settings.server.route_prefix = route_prefix.to_owned();
+        }
+        if !messages.is_empty() {
+            return Err(SettingsError::new(messages));
+        }
+
+        settings.read_secret_files()?;
+        Ok(settings)
+    }
+    pub fn from_file(path: &Path) -> Result<Self, SettingsError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| SettingsError::new(vec![format!("{}: {err}", path.display())]))?;
+        toml::from_str(&content)
+            .map_err(|err| SettingsError::new(vec![format!("{}: {}", path.display(), err.message())]))
+    }
+    fn read_secret_files(&mut self) -> Result<(), SettingsError> {
+        let mut messages = Vec::new();
+        let secrets = [
+            ("database.url", &mut self.database.url, &self.database.url_file),
+            ("database.password", &mut self.database.password, &self.database.password_file),
+            ("tokens.pepper", &mut self.tokens.pepper, &self.tokens.pepper_file),
+            ("mfa.encryption_key", &mut self.mfa.encryption_key, &self.mfa.encryption_key_file),
+            ("admin.password", &mut self.admin.password, &self.admin.password_file),
+        ];
+        for (name, secret, file) in secrets {
+            let Some(file) = file else {
+                continue;
+            };
+            if secret.is_some() {
+                messages.push(format!("{name} and {name}_file are both set"));
+                continue;
+            }
+            // files written by editors or `echo` usually end with a newline
+            match fs::read_to_string(file) {
+                Ok(content) => *secret = Some(Secret::new(content.trim_end_matches(['\r', '\n']))),
+                Err(err) => messages.push(format!("{name}_file {}: {err}", file.display())),
+            }
+        }
+        if !messages.is_empty() {
+            return Err(SettingsError::new(messages));
+        }
+        Ok(())
+    }
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut messages = Vec::new();
+        let mut check = |is_valid: bool, message: &str| {
+            if !is_valid {
+                messages.push(message.to_owned());
+            }
+        };
+
+        check(
+            self.server.address.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()),
+            "server.address must be host:port",
+        );
+        check(
+            self.server.route_prefix.is_empty() || self.server.route_prefix.starts_with('/'),
+            "server.route_prefix must be empty or start with /",
+        );
+
+        check(
+            self.tokens.pepper.as_ref().is_some_and(|pepper| !pepper.expose().is_empty()),
+            "tokens.pepper must be set",
+        );
+        check(self.tokens.refresh_ttl_secs > 0, "tokens.refresh_ttl_secs must be positive");
+        check(self.tokens.password_reset_ttl_secs > 0, "tokens.password_reset_ttl_secs must be positive");
+        check(self.tokens.mfa_challenge_ttl_secs > 0, "tokens.mfa_challenge_ttl_secs must be positive");
+        check(
+            is_cookie_name(&self.tokens.refresh_cookie_name),
+            "tokens.refresh_cookie_name must be a non-empty cookie token",
+        );
+
+        check(!self.jwt.issuer.is_empty(), "jwt.issuer must not be empty");
+        check(self.jwt.ttl_secs > 0, "jwt.ttl_secs must be positive");
+        check(
+            self.jwt.key_rotation_interval_secs.is_none_or(|interval| interval > 0),
+            "jwt.key_rotation_interval_secs must be positive",
+        );
+
+        check(self.password.hashing_concurrency != Some(0), "password.hashing_concurrency must be positive");
+        check(self.password.min_length > 0, "password.min_length must be positive");
+
+        check(self.lockout.max_attempts_per_user > 0, "lockout.max_attempts_per_user must be positive");
+        check(self.lockout.max_attempts_per_ip > 0, "lockout.max_attempts_per_ip must be positive");
+        check(
+            self.lockout.base_delay_secs <= self.lockout.max_delay_secs,
+            "lockout.base_delay_secs must not exceed lockout.max_delay_secs",
+        );
+
+        // only the postgres repository stores TOTP secrets encrypted
+        if self.database.backend == RepositoryBackend::Postgres {
+            if let Err(err) = self.mfa.encryption_key() {
+                check(false, &err);
+            }
+            check(
+                self.database.url.is_none() || (self.database.host.is_none() && self.database.port.is_none()),
+                "database.host and database.port must not be set together with database.url",
+            );
+            check(self.database.connect_timeout_secs != Some(0), "database.connect_timeout_secs must be positive");
+            check(self.database.pool.max_size != Some(0), "database.pool.max_size must be positive");
+        }
+        check(self.notifier.backend.is_some(), "notifier.backend must be set");
+        check(
+            self.admin.username.is_some() == self.admin.password.is_some(),
+            "admin.username and admin.password must be set together",
+        );
+
+        if !messages.is_empty() {
+            return Err(SettingsError::new(messages));
+        }
+        Ok(())
+    }
+}
+
+/// RFC 6265 cookie names are tokens: visible ASCII without separators.
+fn is_cookie_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&byte))
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{get, Data, JsonConfig};
+use actix_web::{App, HttpResponse};
+use serde_json::json;
+
+use common::*;
+
+#[actix_web::test]
+async fn routes_are_mounted_under_the_prefix_next_to_the_host_application() {
+    let context = TestContext::new().await;
+    let app_builder = context.app_builder.clone().with_prefix("/auth/");
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(String::from("host data")))
+            .app_data(JsonConfig::default().limit(16))
+            .route("/health", get().to(|data: Data<String>| async move { HttpResponse::Ok().body(data.to_string()) }))
+            .configure(|cfg| app_builder.configure(cfg)),
+    )
+    .await;
+
+    // larger than the host's JSON limit, the scope brings its own config
+    let body = json!({ "username": "alice", "password": "correct horse" });
+    let request = post_json("/auth/users/register", body.clone()).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+    let request = post_json("/auth/login", body.clone()).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 200);
+    assert!(refresh_cookie(&response).is_some());
+
+    let request = post_json("/login", body).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+    let response = test::call_service(&app, TestRequest::get().uri("/health").to_request()).await;
+    assert_eq!(test::read_body(response).await, "host data");
+}
+
+#[actix_web::test]
+async fn pagination_links_keep_the_prefix() {
+    let context = TestContext::new().await;
+    let app_builder = context.app_builder.clone().with_prefix("/auth");
+    let app = test::init_service(App::new().configure(|cfg| app_builder.configure(cfg))).await;
+    let body = json!({ "username": "alice", "password": "correct horse" });
+    test::call_service(&app, post_json("/auth/users/register", body).to_request()).await;
+    let body = json!({ "username": ADMIN_USERNAME, "password": ADMIN_PASSWORD });
+    let response = test::call_service(&app, post_json("/auth/login", body).to_request()).await;
+    let access_token: String = test::read_body_json(response).await;
+
+    let request = with_bearer(TestRequest::get().uri("/auth/users?limit=1"), &access_token).to_request();
+    let response = test::call_service(&app, request).await;
+    let link = response.headers().get("link").unwrap().to_str().unwrap();
+    assert!(link.starts_with("</auth/users?limit=1"));
+}
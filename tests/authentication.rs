@@ -0,0 +1,342 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, RETRY_AFTER};
+use actix_web::test::{self, TestRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{encode, Header};
+use serde_json::{json, Value};
+
+use common::*;
+
+fn refresh_request(cookie: &Cookie<'_>) -> TestRequest {
+    TestRequest::get().uri("/refresh").cookie(cookie.clone()).peer_addr(peer(1))
+}
+
+#[actix_web::test]
+async fn register_login_refresh_logout_and_protected_access() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    assert_eq!(register(&app, "alice", "correct horse").await.status(), 200);
+
+    let response = login(&app, "alice", "correct horse").await;
+    assert_eq!(response.status(), 200);
+    let cookie = refresh_cookie(&response).expect("login sets the refresh cookie");
+    assert_eq!(cookie.http_only(), Some(true));
+    assert!(cookie.max_age().is_some_and(|max_age| max_age.whole_seconds() > 0));
+    let access_token: String = test::read_body_json(response).await;
+
+    let request = with_bearer(TestRequest::get().uri("/sessions"), &access_token).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 200);
+
+    let response = test::call_service(&app, refresh_request(&cookie).to_request()).await;
+    assert_eq!(response.status(), 200);
+    let rotated_cookie = refresh_cookie(&response).expect("refresh rotates the cookie");
+    assert_ne!(rotated_cookie.value(), cookie.value());
+    let refreshed_access_token: String = test::read_body_json(response).await;
+    let request = with_bearer(TestRequest::get().uri("/sessions"), &refreshed_access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+    let request = TestRequest::post().uri("/logout").cookie(rotated_cookie.clone()).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+    let response = test::call_service(&app, refresh_request(&rotated_cookie).to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+}
+
+#[actix_web::test]
+async fn reusing_a_rotated_refresh_token_revokes_the_family() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (_, cookie) = session(&app, "alice", "correct horse").await;
+
+    let response = test::call_service(&app, refresh_request(&cookie).to_request()).await;
+    let rotated_cookie = refresh_cookie(&response).unwrap();
+
+    let response = test::call_service(&app, refresh_request(&cookie).to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+    let response = test::call_service(&app, refresh_request(&rotated_cookie).to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+}
+
+#[actix_web::test]
+async fn refresh_and_logout_require_a_known_cookie() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/refresh").to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+    let unknown_cookie = Cookie::new(RT_COOKIE_NAME, "not-a-token");
+    let response = test::call_service(&app, refresh_request(&unknown_cookie).to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+    let response = test::call_service(&app, TestRequest::post().uri("/logout").to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+    let request = TestRequest::post().uri("/logout").cookie(unknown_cookie).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+}
+
+#[actix_web::test]
+async fn login_rejects_bad_credentials_and_malformed_bodies() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    let response = login(&app, "alice", "wrong password").await;
+    let wrong_password = assert_problem(response, 401, "unauthorized").await;
+    let response = login(&app, "nobody", "wrong password").await;
+    let unknown_user = assert_problem(response, 401, "unauthorized").await;
+    // both failures look the same, so usernames cannot be enumerated
+    assert_eq!(wrong_password["detail"], unknown_user["detail"]);
+
+    let request = TestRequest::post()
+        .uri("/login")
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{\"username\":")
+        .to_request();
+    assert_problem(test::call_service(&app, request).await, 400, "bad_request").await;
+}
+
+#[actix_web::test]
+async fn repeated_failures_lock_the_account() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    let mut response = login(&app, "alice", "wrong password").await;
+    for _ in 1..=MAX_FAILED_LOGINS {
+        if response.status() == 423 {
+            break;
+        }
+        assert_eq!(response.status(), 401);
+        response = login(&app, "alice", "wrong password").await;
+    }
+    assert!(response.headers().contains_key(RETRY_AFTER));
+    assert_problem(response, 423, "account_locked").await;
+
+    // the correct password does not bypass the lock
+    let response = login(&app, "alice", "correct horse").await;
+    assert_problem(response, 423, "account_locked").await;
+}
+
+#[actix_web::test]
+async fn concurrent_logins_of_one_user_all_succeed() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    // all three load the same version before any of them saves its new session
+    let (first, second, third) = tokio::join!(
+        login(&app, "alice", "correct horse"),
+        login(&app, "alice", "correct horse"),
+        login(&app, "alice", "correct horse"),
+    );
+    for response in [first, second, third] {
+        assert_eq!(response.status(), 200);
+    }
+}
+
+#[actix_web::test]
+async fn concurrent_failed_logins_all_count_towards_the_lockout() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    let (first, second, third) = tokio::join!(
+        login(&app, "alice", "wrong password"),
+        login(&app, "alice", "wrong password"),
+        login(&app, "alice", "wrong password"),
+    );
+    for response in [first, second, third] {
+        assert_ne!(response.status(), 409);
+    }
+    let response = login(&app, "alice", "correct horse").await;
+    assert_problem(response, 423, "account_locked").await;
+}
+
+#[actix_web::test]
+async fn protected_routes_require_a_valid_access_token() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/users/protected").to_request()).await;
+    assert_problem(response, 401, "unauthorized").await;
+    for header in ["Bearer", "Bearer not.a.jwt", "Basic YWxpY2U6cHc=", "bearer"] {
+        let request = TestRequest::get()
+            .uri("/users/protected")
+            .insert_header((AUTHORIZATION, header))
+            .to_request();
+        assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    }
+}
+
+#[actix_web::test]
+async fn expired_access_tokens_are_rejected() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, "alice", "correct horse").await;
+    let payload = access_token.split('.').nth(1).unwrap();
+    let mut claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    let sign = |claims: &Value| {
+        let signing_key = key_store().signing_key();
+        let mut header = Header::new(signing_key.algorithm());
+        header.kid = Some(signing_key.kid().to_owned());
+        encode(&header, claims, signing_key.encoding_key()).unwrap()
+    };
+
+    let request = with_bearer(TestRequest::get().uri("/sessions"), &sign(&claims)).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+    // past the default leeway of a minute
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    claims["iat"] = json!(now - 180);
+    claims["exp"] = json!(now - 120);
+    let request = with_bearer(TestRequest::get().uri("/sessions"), &sign(&claims)).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+}
+
+#[actix_web::test]
+async fn change_password_replaces_the_old_one() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, "alice", "correct horse").await;
+
+    let body = json!({ "old_password": "wrong password", "new_password": "battery staple" });
+    let request = with_bearer(post_json("/password/change", body), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+
+    let body = json!({ "old_password": "correct horse", "new_password": "short" });
+    let request = with_bearer(post_json("/password/change", body), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 422, "validation_failed").await;
+
+    let body = json!({ "old_password": "correct horse", "new_password": "battery staple" });
+    let request = with_bearer(post_json("/password/change", body), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+
+    assert_problem(login(&app, "alice", "correct horse").await, 401, "unauthorized").await;
+    assert_eq!(login(&app, "alice", "battery staple").await.status(), 200);
+}
+
+#[actix_web::test]
+async fn password_reset_tokens_are_single_use() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    // unknown users get the same answer and no message
+    let request = post_json("/password/forgot", json!({ "username": "nobody" })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 202);
+    assert!(context.notifier.last_reset_token("nobody").is_none());
+
+    let request = post_json("/password/forgot", json!({ "username": "alice" })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 202);
+    let reset_token = context.notifier.last_reset_token("alice").expect("reset message was sent");
+
+    let body = json!({ "token": reset_token, "new_password": "short" });
+    let request = post_json("/password/reset", body).to_request();
+    assert_problem(test::call_service(&app, request).await, 422, "validation_failed").await;
+
+    let body = json!({ "token": reset_token, "new_password": "battery staple" });
+    let request = post_json("/password/reset", body.clone()).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+    let request = post_json("/password/reset", body).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+
+    assert_eq!(login(&app, "alice", "battery staple").await.status(), 200);
+}
+
+#[actix_web::test]
+async fn sessions_can_be_listed_and_revoked() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, first_cookie) = session(&app, "alice", "correct horse").await;
+    let (_, second_cookie) = session(&app, "alice", "correct horse").await;
+
+    let request = with_bearer(TestRequest::get().uri("/sessions"), &access_token).to_request();
+    let sessions: Vec<Value> = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.iter().all(|session| session.get("key").is_none()));
+    assert!(sessions.iter().all(|session| {
+        session["created_at"].as_u64().is_some_and(|created_at| session["expire_at"].as_u64() > Some(created_at))
+    }));
+
+    let uri = format!("/sessions/{}", uuid::Uuid::now_v7());
+    let request = with_bearer(TestRequest::delete().uri(&uri), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 404, "not_found").await;
+
+    let uri = format!("/sessions/{}", sessions[0]["id"].as_str().unwrap());
+    let request = with_bearer(TestRequest::delete().uri(&uri), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+
+    let request = with_bearer(TestRequest::post().uri("/logout-all"), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+    for cookie in [first_cookie, second_cookie] {
+        let response = test::call_service(&app, refresh_request(&cookie).to_request()).await;
+        assert_problem(response, 401, "unauthorized").await;
+    }
+}
+
+#[actix_web::test]
+async fn two_factor_login_requires_a_code() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, "alice", "correct horse").await;
+
+    let request = with_bearer(TestRequest::post().uri("/mfa/enroll"), &access_token).to_request();
+    let enrollment: Value = test::call_and_read_body_json(&app, request).await;
+    let secret = enrollment["secret"].as_str().unwrap().to_owned();
+
+    let request = with_bearer(post_json("/mfa/confirm", json!({ "code": "000000x" })), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    let request = with_bearer(post_json("/mfa/confirm", json!({ "code": totp(&secret) })), &access_token).to_request();
+    let confirmation: Value = test::call_and_read_body_json(&app, request).await;
+    let recovery_code = confirmation["recovery_codes"][0].as_str().unwrap().to_owned();
+
+    let challenge = mfa_challenge(&app).await;
+    let body = json!({ "challenge": challenge, "code": "000000x" });
+    let request = post_json("/login/mfa", body).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    // a challenge is single-use, even the right code cannot redeem it again
+    let body = json!({ "challenge": challenge, "code": recovery_code });
+    let request = post_json("/login/mfa", body).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+
+    // recovery codes stand in for the authenticator app
+    let body = json!({ "challenge": mfa_challenge(&app).await, "code": recovery_code });
+    let response = test::call_service(&app, post_json("/login/mfa", body).to_request()).await;
+    assert_eq!(response.status(), 200);
+    assert!(refresh_cookie(&response).is_some());
+}
+
+async fn mfa_challenge<S, B>(app: &S) -> String
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = login(app, "alice", "correct horse").await;
+    assert_eq!(response.status(), 202);
+    assert!(refresh_cookie(&response).is_none());
+    let challenge: Value = test::read_body_json(response).await;
+    assert_eq!(challenge["mfa_required"], true);
+    challenge["challenge"].as_str().unwrap().to_owned()
+}
+
+#[actix_web::test]
+async fn signing_keys_are_published() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+
+    let jwks: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/.well-known/jwks.json").to_request()).await;
+    let keys = jwks["keys"].as_array().unwrap();
+    assert!(!keys.is_empty());
+    assert!(keys.iter().all(|key| key.get("d").is_none()));
+}
@@ -0,0 +1,232 @@
+//! Builds the application through `AppBuilder` as `main.rs` does, but on the in-memory user
+//! repository and a notifier that keeps messages, so the suite runs without
+//! any external service.
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Once, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::test::{self, TestRequest};
+use actix_web::App;
+use async_trait::async_trait;
+use data_encoding::BASE32_NOPAD;
+use ring::hmac;
+use serde_json::{json, Value};
+
+use abcd_layered_architecture::bootstrap;
+use abcd_layered_architecture::api::app_builder::AppBuilder;
+use abcd_layered_architecture::api::auth::signing_key::KeyStore;
+use abcd_layered_architecture::business::auth::login_throttle::LoginThrottle;
+use abcd_layered_architecture::business::auth::notifier::Notifier;
+use abcd_layered_architecture::business::auth::service::AuthService;
+use abcd_layered_architecture::business::error::BusinessError;
+use abcd_layered_architecture::business::user::password_validator::PasswordValidator;
+use abcd_layered_architecture::business::user::service::UserService;
+use abcd_layered_architecture::core::password_policy::PasswordPolicy;
+use abcd_layered_architecture::driver::password::blocking_hasher::BlockingHasher;
+use abcd_layered_architecture::driver::user_repository::in_memory_user_repository::InMemoryUserRepository;
+use abcd_layered_architecture::settings::{NotifierBackend, PasswordAlgorithm, RepositoryBackend, Secret, Settings};
+
+pub const RT_COOKIE_NAME: &str = "refresh-token";
+pub const ADMIN_USERNAME: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "adminpassword1";
+pub const MAX_FAILED_LOGINS: u32 = 3;
+
+/// Keeps the password reset messages so tests can follow the link.
+#[derive(Default)]
+pub struct RecordingNotifier {
+    reset_tokens: Mutex<Vec<(String, String)>>,
+}
+
+impl RecordingNotifier {
+    pub fn last_reset_token(&self, username: &str) -> Option<String> {
+        self.reset_tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(recipient, _)| recipient == username)
+            .map(|(_, token)| token.to_owned())
+    }
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn send_password_reset(&self, username: &str, reset_token: &str) -> Result<(), BusinessError> {
+        self.reset_tokens
+            .lock()
+            .unwrap()
+            .push((username.to_owned(), reset_token.to_owned()));
+        Ok(())
+    }
+}
+
+pub struct TestContext {
+    pub app_builder: AppBuilder,
+    pub notifier: Arc<RecordingNotifier>,
+}
+
+impl TestContext {
+    pub async fn new() -> Self {
+        Self::with_settings(settings()).await
+    }
+    /// Fresh repository and rate limits for every test, only the token
+    /// pepper and the signing keys are shared by the whole process.
+    pub async fn with_settings(settings: Settings) -> Self {
+        static PEPPER: Once = Once::new();
+        PEPPER.call_once(|| abcd_layered_architecture::core::token::init_pepper(b"test-pepper"));
+
+        let user_repositories = Arc::new(InMemoryUserRepository::default());
+        let password_hasher = Arc::new(BlockingHasher::new(
+            bootstrap::password_scheme(&settings.password).unwrap(),
+            settings.password.hashing_concurrency(),
+        ));
+        let password_validator = Arc::new(PasswordValidator::new(
+            PasswordPolicy::new(settings.password.min_length),
+            None,
+        ));
+        let notifier = Arc::new(RecordingNotifier::default());
+        let user_service = Arc::new(UserService::new(
+            user_repositories.clone(),
+            password_hasher.clone(),
+            password_validator.clone(),
+        ));
+        let auth_service = Arc::new(AuthService::new(
+            user_repositories,
+            notifier.clone(),
+            password_hasher,
+            password_validator,
+            settings.lockout.account_policy(),
+            LoginThrottle::new(settings.lockout.client_policy()),
+            settings.tokens.lifetimes(),
+        ));
+        user_service
+            .bootstrap_admin(ADMIN_USERNAME, ADMIN_PASSWORD)
+            .await
+            .expect("could not bootstrap admin user");
+        Self {
+            app_builder: AppBuilder::new(Arc::new(settings), user_service, auth_service, key_store()),
+            notifier,
+        }
+    }
+
+    pub async fn app(
+        &self,
+    ) -> impl Service<actix_http::Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error>
+    {
+        test::init_service(App::new().configure(|cfg| self.app_builder.configure(cfg))).await
+    }
+}
+
+/// Valid settings for the in-memory repository.
+pub fn settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.database.backend = RepositoryBackend::Memory;
+    settings.tokens.pepper = Some(Secret::new("test-pepper"));
+    settings.notifier.backend = Some(NotifierBackend::Log);
+    // the lowest bcrypt cost keeps the suite fast
+    settings.password.algorithm = PasswordAlgorithm::Bcrypt;
+    settings.password.bcrypt_cost = 4;
+    settings.lockout.max_attempts_per_user = MAX_FAILED_LOGINS;
+    settings.lockout.max_attempts_per_ip = 100;
+    settings.validate().expect("test settings are valid");
+    settings
+}
+
+pub fn key_store() -> Arc<KeyStore> {
+    static KEY_STORE: OnceLock<Arc<KeyStore>> = OnceLock::new();
+    KEY_STORE
+        .get_or_init(|| {
+            let directory = std::env::temp_dir().join(format!("abcd-test-keys-{}", std::process::id()));
+            let key_store = KeyStore::load(&directory, jsonwebtoken::Algorithm::ES256, Duration::from_secs(3600))
+                .expect("could not create signing keys");
+            Arc::new(key_store)
+        })
+        .clone()
+}
+
+pub fn peer(last_octet: u8) -> SocketAddr {
+    SocketAddr::from(([10, 0, 0, last_octet], 40000))
+}
+
+pub fn post_json(uri: &str, body: Value) -> TestRequest {
+    TestRequest::post().uri(uri).set_json(body).peer_addr(peer(1))
+}
+
+pub fn with_bearer(request: TestRequest, access_token: &str) -> TestRequest {
+    request.insert_header((AUTHORIZATION, format!("Bearer {access_token}")))
+}
+
+pub fn refresh_cookie<B>(response: &ServiceResponse<B>) -> Option<Cookie<'static>> {
+    response
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == RT_COOKIE_NAME)
+        .map(Cookie::into_owned)
+}
+
+pub async fn register<S, B>(app: &S, username: &str, password: &str) -> ServiceResponse<B>
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let body = json!({ "username": username, "password": password });
+    test::call_service(app, post_json("/users/register", body).to_request()).await
+}
+
+pub async fn login<S, B>(app: &S, username: &str, password: &str) -> ServiceResponse<B>
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let body = json!({ "username": username, "password": password });
+    test::call_service(app, post_json("/login", body).to_request()).await
+}
+
+/// Logs in and returns the access token together with the refresh cookie.
+pub async fn session<S, B>(app: &S, username: &str, password: &str) -> (String, Cookie<'static>)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = login(app, username, password).await;
+    assert_eq!(response.status(), 200, "login of {username} failed");
+    let cookie = refresh_cookie(&response).expect("login sets the refresh cookie");
+    let access_token: String = test::read_body_json(response).await;
+    (access_token, cookie)
+}
+
+/// Reads a problem+json body and checks its status and error code.
+pub async fn assert_problem<B: MessageBody>(response: ServiceResponse<B>, status: u16, code: &str) -> Value {
+    assert_eq!(response.status(), status);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/problem+json"
+    );
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem["status"], status);
+    assert_eq!(problem["code"], code);
+    assert_eq!(problem["type"], format!("urn:abcd:problem:{code}"));
+    problem
+}
+
+/// RFC 6238 code for the current 30 second step.
+pub fn totp(secret: &str) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    let step = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() / 30;
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:06}", binary % 1_000_000)
+}
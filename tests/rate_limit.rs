@@ -0,0 +1,84 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::test::{self, TestRequest};
+use serde_json::json;
+
+use abcd_layered_architecture::api::middleware::rate_limit_store::{Quota, RateLimitStore, ShardedMemoryStore};
+use common::*;
+
+const KEYS: usize = 1000;
+
+#[test]
+fn quota_must_be_positive() {
+    assert!(Quota::new(0, Duration::from_secs(60)).is_err());
+    assert!(Quota::new(1, Duration::ZERO).is_err());
+    assert!(Quota::new(1, Duration::from_nanos(1)).is_ok());
+}
+
+#[actix_web::test]
+async fn short_quotas_do_not_evict_the_buckets_of_long_ones() {
+    let store = ShardedMemoryStore::with_max_keys(32);
+    let hourly = Quota::new(1, Duration::from_secs(60 * 60)).unwrap();
+    let short = Quota::new(1, Duration::from_millis(10)).unwrap();
+    assert!(store.acquire("register:client", &hourly).await.allowed);
+    assert!(!store.acquire("register:client", &hourly).await.allowed);
+
+    for index in 0..KEYS {
+        store.acquire(&format!("users:a{index}"), &short).await;
+    }
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    // every shard is full of refilled short buckets, which are swept now
+    for index in 0..KEYS {
+        store.acquire(&format!("users:b{index}"), &short).await;
+    }
+
+    assert!(!store.acquire("register:client", &hourly).await.allowed);
+}
+
+#[actix_web::test]
+async fn a_full_store_admits_new_keys_by_evicting_the_buckets_that_refill_soonest() {
+    let store = ShardedMemoryStore::with_max_keys(32);
+    let hourly = Quota::new(1, Duration::from_secs(60 * 60)).unwrap();
+    let minutely = Quota::new(1, Duration::from_secs(60)).unwrap();
+    assert!(store.acquire("register:client", &hourly).await.allowed);
+
+    for index in 0..KEYS {
+        assert!(store.acquire(&format!("users:client{index}"), &minutely).await.allowed);
+    }
+
+    assert!(!store.acquire("register:client", &hourly).await.allowed);
+}
+
+#[actix_web::test]
+async fn anonymous_clients_cannot_exhaust_the_key_rotation_limit_of_admins() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    let (access_token, _) = session(&app, ADMIN_USERNAME, ADMIN_PASSWORD).await;
+
+    let request = TestRequest::post().uri("/keys/rotate").peer_addr(peer(1)).to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    let request = TestRequest::post().uri("/keys/rotate").peer_addr(peer(1)).to_request();
+    assert_problem(test::call_service(&app, request).await, 429, "rate_limited").await;
+
+    let request = with_bearer(TestRequest::post().uri("/keys/rotate"), &access_token).peer_addr(peer(1)).to_request();
+    assert!(test::call_service(&app, request).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn registrations_are_capped_across_clients() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    // weak passwords are refused before hashing, so the requests outpace the refill
+    let register_from = |client: u8| {
+        let body = json!({ "username": format!("user{client}"), "password": "short" });
+        post_json("/users/register", body).peer_addr(peer(client)).to_request()
+    };
+
+    for client in 1..=100 {
+        assert_problem(test::call_service(&app, register_from(client)).await, 422, "validation_failed").await;
+    }
+
+    assert_problem(test::call_service(&app, register_from(101)).await, 429, "rate_limited").await;
+}
@@ -0,0 +1,297 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_web::test::{self, TestRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use deadpool_postgres::SslMode;
+use serde_json::Value;
+
+use abcd_layered_architecture::bootstrap;
+use abcd_layered_architecture::driver::database::config_factory::ConfigFactory;
+use abcd_layered_architecture::settings::{Cli, PasswordAlgorithm, RepositoryBackend, Settings};
+use common::*;
+
+const MFA_ENCRYPTION_KEY: &str = "MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDE=";
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("abcd-test-settings-{}-{name}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn env(variables: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let variables: HashMap<String, String> = variables
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    move |name| variables.get(name).cloned()
+}
+
+fn cli(config: Option<PathBuf>, overrides: &[(&str, &str)]) -> Cli {
+    Cli {
+        config,
+        overrides: overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        ..Cli::default()
+    }
+}
+
+#[test]
+fn command_line_overrides_environment_overrides_file() {
+    let directory = directory("layers");
+    let file = directory.join("config.toml");
+    fs::write(
+        &file,
+        r#"
+        [server]
+        address = "0.0.0.0:9000"
+        route_prefix = "/auth"
+
+        [database]
+        host = "db"
+        port = 6543
+        dbname = "app"
+
+        [tokens]
+        pepper = "from-file"
+        refresh_ttl_secs = 120
+
+        [jwt]
+        issuer = "from-file"
+        key_algorithm = "EdDSA"
+
+        [password]
+        algorithm = "bcrypt"
+        bcrypt_cost = 10
+
+        [mfa]
+        encryption_key = "MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDE="
+        "#,
+    )
+    .unwrap();
+
+    let env = env(&[
+        ("JWT_ISSUER", "from-env"),
+        ("PG_HOST", "from-env"),
+        ("BCRYPT_COST", "11"),
+        ("NOTIFIER_BACKEND", "log"),
+    ]);
+    let mut cli = cli(Some(file), &[("BCRYPT_COST", "12")]);
+    cli.address = Some("127.0.0.1:7000".to_owned());
+    let settings = bootstrap::load_settings(&cli, env).unwrap();
+
+    assert_eq!(settings.server.address, "127.0.0.1:7000");
+    assert_eq!(settings.server.route_prefix, "/auth");
+    assert_eq!(settings.database.backend, RepositoryBackend::Postgres);
+    assert_eq!(settings.database.host.as_deref(), Some("from-env"));
+    assert_eq!(settings.database.port, Some(6543));
+    assert_eq!(settings.tokens.pepper.unwrap().expose(), "from-file");
+    assert_eq!(settings.tokens.refresh_ttl_secs, 120);
+    // untouched settings keep their defaults
+    assert_eq!(settings.tokens.password_reset_ttl_secs, 15 * 60);
+    assert_eq!(settings.tokens.refresh_cookie_name, "refresh-token");
+    assert_eq!(settings.jwt.issuer, "from-env");
+    assert_eq!(settings.jwt.key_algorithm, jsonwebtoken::Algorithm::EdDSA);
+    assert_eq!(settings.password.algorithm, PasswordAlgorithm::Bcrypt);
+    assert_eq!(settings.password.bcrypt_cost, 12);
+}
+
+#[test]
+fn secrets_are_read_from_files() {
+    let directory = directory("secrets");
+    fs::write(directory.join("pepper"), "from-secret-file\n").unwrap();
+    fs::write(directory.join("mfa_key"), MFA_ENCRYPTION_KEY).unwrap();
+    let file = directory.join("config.toml");
+    let toml = format!(
+        "[mfa]\nencryption_key_file = {:?}\n",
+        directory.join("mfa_key").to_str().unwrap()
+    );
+    fs::write(&file, toml).unwrap();
+
+    let pepper_file = directory.join("pepper");
+    let env = env(&[
+        ("PG_DBNAME", "app"),
+        ("NOTIFIER_BACKEND", "log"),
+        ("TOKEN_PEPPER", "from-env"),
+        ("TOKEN_PEPPER_FILE", pepper_file.to_str().unwrap()),
+    ]);
+    let settings = bootstrap::load_settings(&cli(Some(file), &[]), env).unwrap();
+
+    assert_eq!(settings.tokens.pepper.as_ref().unwrap().expose(), "from-secret-file");
+    assert_eq!(settings.mfa.encryption_key().unwrap().len(), 32);
+    assert!(!format!("{settings:?}").contains("from-secret-file"));
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let env = env(&[
+        ("ADDRESS", "8000"),
+        ("ROUTE_PREFIX", "auth"),
+        ("BCRYPT_COST", "3"),
+        ("PASSWORD_HASH_ALGORITHM", "bcrypt"),
+        ("RT_COOKIE_NAME", "refresh token"),
+        ("LOGIN_LOCKOUT_BASE_SECS", "7200"),
+        ("MFA_ENCRYPTION_KEY", "c2hvcnQ="),
+        ("ADMIN_USERNAME", "admin"),
+    ]);
+    let err = bootstrap::load_settings(&Cli::default(), env).unwrap_err();
+    let messages = err.messages().join("\n");
+
+    for expected in [
+        "server.address",
+        "server.route_prefix",
+        "tokens.pepper",
+        "tokens.refresh_cookie_name",
+        "bcrypt_cost",
+        "lockout.base_delay_secs",
+        "mfa.encryption_key",
+        "database: invalid configuration",
+        "notifier.backend",
+        "admin.username",
+    ] {
+        assert!(messages.contains(expected), "{expected} missing in:\n{messages}");
+    }
+    assert_eq!(err.messages().len(), 10, "{messages}");
+}
+
+#[test]
+fn malformed_input_names_its_source() {
+    let err = bootstrap::load_settings(&Cli::default(), env(&[("PG_PORT", "five")])).unwrap_err();
+    assert!(err.messages()[0].starts_with("PG_PORT: "));
+
+    let err = bootstrap::load_settings(&cli(None, &[("PG_PROT", "5432")]), env(&[])).unwrap_err();
+    assert_eq!(err.messages(), ["--set PG_PROT: unknown setting"]);
+
+    let file = directory("malformed").join("config.toml");
+    fs::write(&file, "[jwt]\nissuer = \"abcd\"\nttl = 60\n").unwrap();
+    let err = bootstrap::load_settings(&cli(Some(file.clone()), &[]), env(&[])).unwrap_err();
+    assert!(err.messages()[0].starts_with(file.to_str().unwrap()));
+    assert!(err.messages()[0].contains("unknown field `ttl`"));
+
+    let err = bootstrap::load_settings(&cli(Some(file.with_extension("missing")), &[]), env(&[])).unwrap_err();
+    assert_eq!(err.messages().len(), 1);
+
+    let file = directory("malformed").join("secret_file.toml");
+    fs::write(&file, "[tokens]\npepper = \"a\"\npepper_file = \"/run/secrets/pepper\"\n").unwrap();
+    let err = bootstrap::load_settings(&cli(Some(file), &[]), env(&[])).unwrap_err();
+    assert_eq!(err.messages(), ["tokens.pepper and tokens.pepper_file are both set"]);
+}
+
+#[test]
+fn secrets_are_not_accepted_on_the_command_line() {
+    let overrides = [("TOKEN_PEPPER", "hunter2"), ("PG_PASSWORD", "hunter2"), ("ADMIN_PASSWORD", "hunter2")];
+    let err = bootstrap::load_settings(&cli(None, &overrides), env(&[])).unwrap_err();
+    assert_eq!(err.messages().len(), 3, "{err}");
+    assert!(err.messages()[0].starts_with("--set TOKEN_PEPPER: "));
+    assert!(!err.to_string().contains("hunter2"), "{err}");
+
+    let file = directory("command_line_secrets").join("pepper");
+    fs::write(&file, "pepper").unwrap();
+    let overrides = [
+        ("TOKEN_PEPPER_FILE", file.to_str().unwrap()),
+        ("USER_REPOSITORY", "memory"),
+        ("NOTIFIER_BACKEND", "log"),
+    ];
+    let settings = bootstrap::load_settings(&cli(None, &overrides), env(&[])).unwrap();
+    assert_eq!(settings.tokens.pepper.unwrap().expose(), "pepper");
+}
+
+#[test]
+fn database_url_is_combined_with_the_pool_settings() {
+    let env = env(&[
+        ("TOKEN_PEPPER", "pepper"),
+        ("MFA_ENCRYPTION_KEY", MFA_ENCRYPTION_KEY),
+        ("NOTIFIER_BACKEND", "log"),
+        ("DATABASE_URL", "postgres://app:secret@db:6543/app?sslmode=verify-full&application_name=abcd"),
+        ("PG_USER", "other"),
+        ("PG_POOL_MAX_SIZE", "4"),
+        ("PG_POOL_WAIT_TIMEOUT_SECS", "2"),
+        ("PG_CONNECT_TIMEOUT_SECS", "5"),
+    ]);
+    let settings = bootstrap::load_settings(&Cli::default(), env).unwrap();
+    assert!(!format!("{settings:?}").contains("secret"));
+
+    let config = ConfigFactory::new(settings.database).create().unwrap();
+    // the libpq TLS parameters are handled by the connector
+    assert_eq!(config.url.as_deref(), Some("postgres://app:secret@db:6543/app?application_name=abcd"));
+    assert_eq!(config.ssl_mode, Some(SslMode::Require));
+    let pool = config.pool.as_ref().unwrap();
+    assert_eq!(pool.max_size, 4);
+    assert_eq!(pool.timeouts.wait, Some(Duration::from_secs(2)));
+    assert_eq!(pool.timeouts.create, None);
+    let pg_config = config.get_pg_config().unwrap();
+    assert_eq!(pg_config.get_user(), Some("other"));
+    assert_eq!(pg_config.get_dbname(), Some("app"));
+    assert_eq!(pg_config.get_connect_timeout(), Some(&Duration::from_secs(5)));
+}
+
+#[test]
+fn unusable_database_settings_are_reported() {
+    let missing = directory("database").join("missing.pem");
+    for (variables, expected) in [
+        (vec![("DATABASE_URL", "postgres://db/app?sslmode=verify")], "unknown ssl mode: verify"),
+        (vec![("DATABASE_URL", "postgres://db/app"), ("PG_HOST", "db")], "database.host"),
+        (vec![("PG_DBNAME", "app"), ("PG_SSLROOTCERT", missing.to_str().unwrap())], "missing.pem"),
+        (vec![("DATABASE_URL", "postgres://db/app?sslcert=client.pem")], "ssl_cert and ssl_key"),
+        (vec![("PG_DBNAME", "app"), ("PG_POOL_MAX_SIZE", "0")], "database.pool.max_size"),
+    ] {
+        let mut variables = variables;
+        variables.extend([
+            ("TOKEN_PEPPER", "pepper"),
+            ("MFA_ENCRYPTION_KEY", MFA_ENCRYPTION_KEY),
+            ("NOTIFIER_BACKEND", "log"),
+        ]);
+        let err = bootstrap::load_settings(&Cli::default(), env(&variables)).unwrap_err();
+        assert_eq!(err.messages().len(), 1, "{err}");
+        assert!(err.messages()[0].contains(expected), "{expected} missing in: {err}");
+    }
+}
+
+#[actix_web::test]
+async fn cookie_name_and_token_claims_follow_the_settings() {
+    let mut settings = settings();
+    settings.tokens.refresh_cookie_name = "__Host-session".to_owned();
+    settings.tokens.refresh_ttl_secs = 120;
+    settings.jwt.issuer = "https://auth.example.com".to_owned();
+    settings.jwt.ttl_secs = 60;
+    let context = TestContext::with_settings(settings).await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+
+    let response = login(&app, "alice", "correct horse").await;
+    let cookie = response
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "__Host-session")
+        .expect("login sets the configured cookie")
+        .into_owned();
+    assert!(cookie.max_age().is_some_and(|max_age| max_age.whole_seconds() <= 120));
+    let access_token: String = test::read_body_json(response).await;
+    let payload = access_token.split('.').nth(1).unwrap();
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    assert_eq!(claims["iss"], "https://auth.example.com");
+    assert_eq!(claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap(), 60);
+
+    let request = TestRequest::get().uri("/refresh").cookie(cookie).peer_addr(peer(1)).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+}
+
+#[test]
+fn example_file_lists_the_defaults() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+    let env = env(&[("TOKEN_PEPPER", "pepper"), ("USER_REPOSITORY", "memory"), ("NOTIFIER_BACKEND", "log")]);
+    let settings = bootstrap::load_settings(&cli(Some(file), &[]), env).unwrap();
+
+    let mut defaults = Settings::default();
+    defaults.database.backend = RepositoryBackend::Memory;
+    defaults.tokens.pepper = settings.tokens.pepper.clone();
+    defaults.notifier.backend = settings.notifier.backend;
+    assert_eq!(format!("{settings:?}"), format!("{defaults:?}"));
+}
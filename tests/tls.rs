@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, DnType, IsCa, KeyPair};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::ServerConfig;
+use tokio::io::DuplexStream;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_rustls::TlsAcceptor;
+
+use abcd_layered_architecture::driver::database::tls_factory::TlsFactory;
+use abcd_layered_architecture::settings::SslMode;
+
+/// A CA that issued the server certificate for `localhost`, and another one
+/// the client may wrongly trust. Both are written as PEM files.
+struct Pki {
+    ca: PathBuf,
+    other_ca: PathBuf,
+    server_config: Arc<ServerConfig>,
+}
+
+impl Pki {
+    fn new(name: &str) -> Self {
+        let directory = std::env::temp_dir().join(format!("abcd-tls-{}-{name}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let ca = certificate_authority("test-ca");
+        let other_ca = certificate_authority("other-ca");
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".to_owned()])
+            .unwrap()
+            .signed_by(&server_key, &ca)
+            .unwrap();
+        let server_config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![server_cert.der().clone(), ca.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server_key.serialize_der())),
+            )
+            .unwrap();
+        fs::write(directory.join("ca.crt"), ca.pem()).unwrap();
+        fs::write(directory.join("other.crt"), other_ca.pem()).unwrap();
+        Self {
+            ca: directory.join("ca.crt"),
+            other_ca: directory.join("other.crt"),
+            server_config: Arc::new(server_config),
+        }
+    }
+    /// Runs a TLS handshake against the server over an in-memory stream,
+    /// `true` if the client accepted the server certificate.
+    async fn handshake(&self, ssl_mode: SslMode, root_cert: Option<&PathBuf>, host: &str) -> bool {
+        let mut connector = TlsFactory::new(ssl_mode, root_cert.cloned(), None).create().unwrap();
+        let tls_connect = MakeTlsConnect::<DuplexStream>::make_tls_connect(&mut connector, host).unwrap();
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let acceptor = TlsAcceptor::from(self.server_config.clone());
+        let (result, _) = tokio::join!(tls_connect.connect(client), acceptor.accept(server));
+        result.is_ok()
+    }
+}
+
+fn certificate_authority(common_name: &str) -> CertifiedIssuer<'static, KeyPair> {
+    let mut params = CertificateParams::new(Vec::new()).unwrap();
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name.push(DnType::CommonName, common_name);
+    CertifiedIssuer::self_signed(params, KeyPair::generate().unwrap()).unwrap()
+}
+
+#[actix_web::test]
+async fn verify_full_checks_the_chain_and_the_host_name() {
+    let pki = Pki::new("verify-full");
+
+    assert!(pki.handshake(SslMode::VerifyFull, Some(&pki.ca), "localhost").await);
+    assert!(!pki.handshake(SslMode::VerifyFull, Some(&pki.ca), "db.example").await);
+    assert!(!pki.handshake(SslMode::VerifyFull, Some(&pki.other_ca), "localhost").await);
+    // the public roots do not know the test CA
+    assert!(!pki.handshake(SslMode::VerifyFull, None, "localhost").await);
+}
+
+#[actix_web::test]
+async fn verify_ca_accepts_any_host_name_but_only_a_trusted_chain() {
+    let pki = Pki::new("verify-ca");
+
+    assert!(pki.handshake(SslMode::VerifyCa, Some(&pki.ca), "db.example").await);
+    assert!(!pki.handshake(SslMode::VerifyCa, Some(&pki.other_ca), "localhost").await);
+}
+
+#[actix_web::test]
+async fn a_root_certificate_makes_require_and_prefer_verify_the_chain() {
+    let pki = Pki::new("require");
+
+    for ssl_mode in [SslMode::Require, SslMode::Prefer] {
+        assert!(pki.handshake(ssl_mode, None, "db.example").await);
+        assert!(pki.handshake(ssl_mode, Some(&pki.ca), "db.example").await);
+        assert!(!pki.handshake(ssl_mode, Some(&pki.other_ca), "localhost").await);
+    }
+}
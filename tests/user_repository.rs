@@ -0,0 +1,42 @@
+mod common;
+
+use abcd_layered_architecture::bootstrap;
+use abcd_layered_architecture::business::user::repository::{RepositoryError, UserRepository, UserRepositoryFactory};
+use abcd_layered_architecture::core::role::Role;
+use abcd_layered_architecture::core::user::User;
+use abcd_layered_architecture::driver::password::blocking_hasher::BlockingHasher;
+use abcd_layered_architecture::driver::user_repository::in_memory_user_repository::InMemoryUserRepository;
+use common::*;
+
+#[actix_web::test]
+async fn updating_a_stale_user_fails_without_overwriting_the_newer_one() {
+    let settings = settings();
+    let hasher = BlockingHasher::new(bootstrap::password_scheme(&settings.password).unwrap(), 1);
+    let repository = InMemoryUserRepository::default();
+    let user = User::new("alice".to_owned(), "correct horse".to_owned(), &hasher).await;
+    repository.create(&user).await.unwrap();
+
+    let mut first = repository.find_by_username("alice").await.unwrap().unwrap();
+    let mut second = repository.find_by_username("alice").await.unwrap().unwrap();
+    first.grant(Role::Admin);
+    repository.update(&first).await.unwrap();
+    second.logout_all();
+    assert!(matches!(repository.update(&second).await, Err(RepositoryError::Stale)));
+
+    let mut reloaded = repository.find_by_username("alice").await.unwrap().unwrap();
+    assert!(reloaded.to_dto().roles().contains(&Role::Admin));
+    reloaded.logout_all();
+    repository.update(&reloaded).await.unwrap();
+}
+
+#[actix_web::test]
+async fn repositories_opened_for_different_requests_see_the_same_users() {
+    let settings = settings();
+    let hasher = BlockingHasher::new(bootstrap::password_scheme(&settings.password).unwrap(), 1);
+    let factory = InMemoryUserRepository::default();
+    let user = User::new("alice".to_owned(), "correct horse".to_owned(), &hasher).await;
+    factory.open().await.unwrap().create(&user).await.unwrap();
+
+    let repository = factory.open().await.unwrap();
+    assert!(repository.find_by_username("alice").await.unwrap().is_some());
+}
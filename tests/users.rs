@@ -0,0 +1,166 @@
+mod common;
+
+use actix_web::http::header::LINK;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use common::*;
+
+#[actix_web::test]
+async fn register_rejects_duplicates_weak_passwords_and_malformed_bodies() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+
+    assert_eq!(register(&app, "alice", "correct horse").await.status(), 200);
+    assert_problem(register(&app, "alice", "correct horse").await, 409, "conflict").await;
+
+    let problem = assert_problem(register(&app, "bob", "short").await, 422, "validation_failed").await;
+    assert!(problem["errors"].as_array().is_some_and(|errors| !errors.is_empty()));
+
+    let request = TestRequest::post()
+        .uri("/users/register")
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{\"username\": 42}")
+        .peer_addr(peer(2))
+        .to_request();
+    assert_problem(test::call_service(&app, request).await, 400, "bad_request").await;
+}
+
+#[actix_web::test]
+async fn register_is_rate_limited_per_client() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+
+    for index in 0..5 {
+        let body = json!({ "username": format!("user{index}"), "password": "correct horse" });
+        let request = post_json("/users/register", body).peer_addr(peer(3)).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 200);
+    }
+    let body = json!({ "username": "user5", "password": "correct horse" });
+    let response = test::call_service(&app, post_json("/users/register", body.clone()).peer_addr(peer(3)).to_request()).await;
+    assert!(response.headers().contains_key("retry-after"));
+    assert_problem(response, 429, "rate_limited").await;
+
+    // other clients keep their own budget
+    let response = test::call_service(&app, post_json("/users/register", body).peer_addr(peer(4)).to_request()).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[actix_web::test]
+async fn managing_users_requires_the_admin_role() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, "alice", "correct horse").await;
+
+    let request = TestRequest::get().uri("/users").to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    let request = with_bearer(TestRequest::get().uri("/users"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+    let request = with_bearer(TestRequest::get().uri("/users/protected"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+
+    let user_id = uuid::Uuid::now_v7();
+    let request = with_bearer(TestRequest::get().uri(&format!("/users/{user_id}")), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+    let request = with_bearer(post_json("/users/delete", json!({ "user_id": user_id })), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+    let body = json!({ "user_id": user_id, "role": "admin" });
+    let request = with_bearer(post_json("/users/roles", body), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+    let request = with_bearer(TestRequest::post().uri("/keys/rotate"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+}
+
+#[actix_web::test]
+async fn metrics_are_only_served_to_admins() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, "alice", "correct horse").await;
+    let (admin_access_token, _) = session(&app, ADMIN_USERNAME, ADMIN_PASSWORD).await;
+
+    let request = TestRequest::get().uri("/metrics").to_request();
+    assert_problem(test::call_service(&app, request).await, 401, "unauthorized").await;
+    let request = with_bearer(TestRequest::get().uri("/metrics"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 403, "forbidden").await;
+    let request = with_bearer(TestRequest::get().uri("/metrics"), &admin_access_token).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 200);
+    let body = test::read_body(response).await;
+    assert!(String::from_utf8_lossy(&body).contains("statement_cache_hits_total"));
+}
+
+#[actix_web::test]
+async fn admin_pages_through_users() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    for username in ["alice", "bob", "carol"] {
+        register(&app, username, "correct horse").await;
+    }
+    let (access_token, _) = session(&app, ADMIN_USERNAME, ADMIN_PASSWORD).await;
+
+    let request = with_bearer(TestRequest::get().uri("/users?limit=2"), &access_token).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 200);
+    let link = response.headers().get(LINK).expect("first page links the next one").to_str().unwrap().to_owned();
+    let page: Value = test::read_body_json(response).await;
+    let users = page["users"].as_array().unwrap();
+    assert_eq!(users.len(), 2);
+    assert!(users.iter().all(|user| user.get("password").is_none() && user.get("tokens").is_none()));
+    assert!(link.contains(page["next_cursor"].as_str().unwrap()));
+
+    let uri = link.trim_start_matches('<').split('>').next().unwrap().to_owned();
+    let request = with_bearer(TestRequest::get().uri(&uri), &access_token).to_request();
+    let response = test::call_service(&app, request).await;
+    assert!(response.headers().get(LINK).is_none());
+    let page: Value = test::read_body_json(response).await;
+    assert_eq!(page["users"].as_array().unwrap().len(), 2);
+    assert!(page["next_cursor"].is_null());
+
+    let request = with_bearer(TestRequest::get().uri("/users?username_prefix=ca"), &access_token).to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["users"][0]["username"], "carol");
+
+    let request = with_bearer(TestRequest::get().uri("/users?limit=ten"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 400, "bad_request").await;
+}
+
+#[actix_web::test]
+async fn admin_shows_grants_and_deletes_users() {
+    let context = TestContext::new().await;
+    let app = context.app().await;
+    register(&app, "alice", "correct horse").await;
+    let (access_token, _) = session(&app, ADMIN_USERNAME, ADMIN_PASSWORD).await;
+
+    let request = with_bearer(TestRequest::get().uri("/users?username_prefix=alice"), &access_token).to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    let user_id = page["users"][0]["id"].as_str().unwrap().to_owned();
+
+    let request = with_bearer(TestRequest::get().uri(&format!("/users/{user_id}")), &access_token).to_request();
+    let user: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(user["username"], "alice");
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    assert!(user["created_at"].as_u64().is_some_and(|created_at| now.abs_diff(created_at) < 60), "{user}");
+    let request = with_bearer(TestRequest::get().uri("/users/not-a-uuid"), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 400, "bad_request").await;
+    let uri = format!("/users/{}", uuid::Uuid::now_v7());
+    let request = with_bearer(TestRequest::get().uri(&uri), &access_token).to_request();
+    assert_problem(test::call_service(&app, request).await, 404, "not_found").await;
+
+    let body = json!({ "user_id": user_id, "role": "admin" });
+    let request = with_bearer(post_json("/users/roles", body), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+    let (alice_access_token, _) = session(&app, "alice", "correct horse").await;
+    let body = json!({ "user_id": user_id, "role": "user" });
+    let request = with_bearer(post_json("/users/roles", body), &alice_access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+
+    let body = json!({ "user_id": user_id });
+    let request = with_bearer(post_json("/users/delete", body.clone()), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+    // deleting is idempotent
+    let request = with_bearer(post_json("/users/delete", body), &access_token).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 204);
+    assert_problem(login(&app, "alice", "correct horse").await, 401, "unauthorized").await;
+}